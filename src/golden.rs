@@ -0,0 +1,294 @@
+//! Scripted scenarios for checking that the simulation still plays out exactly the way it used to.
+//!
+//! A scenario builds a world, places material on the ticks it is told to, and steps the world
+//! forward, keeping the `state_hash` and an ASCII picture of every tick. `Scenario::record` saves
+//! those to a fixture file, and `Scenario::check` runs the scenario again and compares it against
+//! the fixture, describing the first tick that went differently.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::{Material, World};
+
+/// How many cells to either side of the first difference are drawn when a check fails.
+const DIFF_REACH_X: usize = 8;
+const DIFF_REACH_Y: usize = 4;
+
+enum Step {
+    Place {
+        x: usize,
+        y: usize,
+        material: Material,
+    },
+    Fill {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+    },
+}
+
+/// The world as it was at the end of a tick.
+pub struct Frame {
+    pub hash: u64,
+    /// One line of glyphs per row of the world.
+    pub rows: Vec<String>,
+}
+
+pub struct Scenario {
+    width: usize,
+    height: usize,
+    chunk_size: usize,
+    /// Passed to `World::set_stripe_rows`.
+    stripe_rows: u32,
+    ticks: u64,
+    /// Edits to make at the start of a tick, before it is simulated, in the order they were added.
+    steps: Vec<(u64, Step)>,
+}
+
+impl Scenario {
+    pub fn new(width: usize, height: usize, chunk_size: usize) -> Scenario {
+        Scenario {
+            width,
+            height,
+            chunk_size,
+            stripe_rows: 0,
+            ticks: 0,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Places a single cell of `material`, with its default spread, at the start of `tick`.
+    pub fn place(mut self, tick: u64, x: usize, y: usize, material: Material) -> Scenario {
+        self.steps.push((tick, Step::Place { x, y, material }));
+        self
+    }
+
+    /// Fills a rectangle with `material`, with its default spread, at the start of `tick`.
+    pub fn fill(
+        mut self,
+        tick: u64,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+    ) -> Scenario {
+        self.steps.push((
+            tick,
+            Step::Fill {
+                x,
+                y,
+                width,
+                height,
+                material,
+            },
+        ));
+        self
+    }
+
+    /// Splits every tick into stripes of `rows` chunk rows.
+    pub fn stripes(mut self, rows: u32) -> Scenario {
+        self.stripe_rows = rows;
+        self
+    }
+
+    /// Sets how many ticks the scenario runs for.
+    pub fn run_for(mut self, ticks: u64) -> Scenario {
+        self.ticks = ticks;
+        self
+    }
+
+    /// Plays the scenario out, returning the world as it was at the end of every tick.
+    pub fn run(&self) -> Vec<Frame> {
+        let mut world = World::create(self.width, self.height, self.chunk_size);
+
+        world.set_stripe_rows(self.stripe_rows);
+
+        let mut frames = Vec::with_capacity(self.ticks as usize);
+
+        for tick in 0..self.ticks {
+            for (_, step) in self.steps.iter().filter(|(at, _)| *at == tick) {
+                match *step {
+                    Step::Place { x, y, material } => {
+                        world.place_default(x, y, material);
+                    }
+                    Step::Fill {
+                        x,
+                        y,
+                        width,
+                        height,
+                        material,
+                    } => {
+                        for j in y..y.saturating_add(height) {
+                            for i in x..x.saturating_add(width) {
+                                world.place_default(i, j, material);
+                            }
+                        }
+                    }
+                }
+            }
+
+            world.simulate();
+
+            frames.push(Frame {
+                hash: world.state_hash(),
+                rows: draw(&world),
+            });
+        }
+
+        frames
+    }
+
+    /// Runs the scenario and saves every tick of it to a fixture file at `path`.
+    pub fn record<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut fixture = String::new();
+
+        for (tick, frame) in self.run().iter().enumerate() {
+            let _ = writeln!(fixture, "tick {} {:016x}", tick, frame.hash);
+
+            for row in frame.rows.iter() {
+                fixture.push_str(row);
+                fixture.push('\n');
+            }
+        }
+
+        fs::write(path.as_ref(), fixture)
+            .map_err(|error| format!("could not write {}: {}", path.as_ref().display(), error))
+    }
+
+    /// Runs the scenario and compares every tick against the fixture file at `path`. Fails with a
+    /// description of the first tick that went differently, including a picture of the cells
+    /// around the first one that differs.
+    pub fn check<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let fixture = fs::read_to_string(path.as_ref())
+            .map_err(|error| format!("could not read {}: {}", path.as_ref().display(), error))?;
+        let expected = parse(&fixture)?;
+        let actual = self.run();
+
+        if expected.len() != actual.len() {
+            return Err(format!(
+                "the fixture has {} ticks, but the scenario ran for {}",
+                expected.len(),
+                actual.len()
+            ));
+        }
+
+        for (tick, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+            if expected.hash != actual.hash {
+                return Err(format!(
+                    "tick {} diverged: expected hash {:016x}, got {:016x}\n{}",
+                    tick,
+                    expected.hash,
+                    actual.hash,
+                    diff(&expected.rows, &actual.rows)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn glyph(material: Material) -> char {
+    match material {
+        Material::Air => '.',
+        Material::Rock => '#',
+        Material::Sand => ':',
+        Material::Water => '~',
+        Material::Smoke => '%',
+        Material::Oil => 'o',
+        Material::Acid => 'a',
+        Material::Steam => '^',
+        Material::Fire => '*',
+        Material::Fuse => '=',
+        Material::Virus => 'v',
+        Material::Ice => 'i',
+    }
+}
+
+fn draw(world: &World) -> Vec<String> {
+    world
+        .materials
+        .chunks(world.size.width.max(1))
+        .map(|row| row.iter().map(|&material| glyph(material)).collect())
+        .collect()
+}
+
+fn parse(fixture: &str) -> Result<Vec<Frame>, String> {
+    let mut frames: Vec<Frame> = Vec::new();
+
+    for line in fixture.lines() {
+        if let Some(header) = line.strip_prefix("tick ") {
+            let hash = header
+                .split_whitespace()
+                .nth(1)
+                .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                .ok_or_else(|| format!("\"{}\" is not a valid tick", line))?;
+
+            frames.push(Frame {
+                hash,
+                rows: Vec::new(),
+            });
+        } else {
+            match frames.last_mut() {
+                Some(frame) => frame.rows.push(line.to_string()),
+                None => return Err("the fixture does not start with a tick".to_string()),
+            }
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Draws the cells around the first one that differs, expected on the left and actual on the
+/// right, with the differing rows marked.
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let expected: Vec<Vec<char>> = expected.iter().map(|row| row.chars().collect()).collect();
+    let actual: Vec<Vec<char>> = actual.iter().map(|row| row.chars().collect()).collect();
+
+    let first = expected
+        .iter()
+        .zip(actual.iter())
+        .enumerate()
+        .find_map(|(y, (a, b))| {
+            (0..a.len().max(b.len()))
+                .find(|&x| a.get(x) != b.get(x))
+                .map(|x| (x, y))
+        });
+
+    let (x, y) = match first {
+        Some(first) => first,
+        None => return "every cell matches, so only a tint or spread differs".to_string(),
+    };
+
+    let left = x.saturating_sub(DIFF_REACH_X);
+    let right = x + DIFF_REACH_X + 1;
+    let top = y.saturating_sub(DIFF_REACH_Y);
+    let bottom = (y + DIFF_REACH_Y + 1).min(expected.len().max(actual.len()));
+
+    let slice = |rows: &[Vec<char>], j: usize| -> String {
+        (left..right)
+            .map(|i| {
+                rows.get(j)
+                    .and_then(|row| row.get(i))
+                    .copied()
+                    .unwrap_or(' ')
+            })
+            .collect()
+    };
+
+    let mut picture = format!("first difference at ({}, {}), expected | actual:\n", x, y);
+
+    for j in top..bottom {
+        let a = slice(&expected, j);
+        let b = slice(&actual, j);
+        let marker = if a != b { '>' } else { ' ' };
+
+        let _ = writeln!(picture, "{} {:>4} {} | {}", marker, j, a, b);
+    }
+
+    picture
+}
@@ -1,5 +1,15 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "golden")]
+pub mod golden;
+#[cfg(test)]
+mod tests;
+
 // #[wasm_bindgen]
 // extern "C" {
 //     #[wasm_bindgen(js_namespace = console)]
@@ -10,16 +20,414 @@ use wasm_bindgen::prelude::*;
 //     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 // }
 
+/// Ambient temperature, in degrees Celsius, of a newly created world.
+const DEFAULT_AMBIENT_TEMPERATURE: i16 = 20;
+
+/// Temperatures, in degrees Celsius, that fire, steam and ice start out at when placed.
+const FIRE_TEMPERATURE: i16 = 600;
+const STEAM_TEMPERATURE: i16 = 100;
+const ICE_TEMPERATURE: i16 = -10;
+
+/// Ambient temperature, in degrees Celsius, above which exposed water may evaporate.
+const EVAPORATION_POINT: i16 = 30;
+/// An exposed water cell evaporates on average once every this many ticks.
+const EVAPORATION_ODDS: u64 = 600;
+
+/// Ambient temperature below which water resting on a cold floor, or touching ice, may freeze.
+const FREEZING_POINT: i16 = 0;
+/// Ambient temperature above which ice may melt, a little above freezing so pools do not flicker.
+const MELTING_POINT: i16 = 4;
+/// A freezing water cell turns to ice on average once every this many ticks.
+const FREEZE_ODDS: u64 = 12;
+/// A melting ice cell turns to water on average once every this many ticks.
+const MELT_ODDS: u64 = 120;
+
+/// A rock cell touching flowing water erodes on average once every this many ticks.
+const EROSION_ODDS: u64 = 2000;
+
+/// Acid eats into a random neighbor on average once every this many ticks.
+const CORROSION_ODDS: u64 = 12;
+/// One in this many solid cells eaten by acid gives off smoke instead of vanishing.
+const CORROSION_SMOKE_ODDS: u64 = 4;
+/// Acid touching at least this many water cells is diluted into water.
+const DILUTION_NEIGHBORS: usize = 2;
+/// Diluted acid turns into water on average once every this many ticks.
+const DILUTION_ODDS: u64 = 4;
+
+/// With surface tension, the fewest water neighbors, diagonals included, water may flow next to.
+const TENSION_NEIGHBORS: usize = 2;
+
+/// How far along its row a gas cell trapped under a ceiling looks for an opening to rise through.
+const OPENING_REACH: usize = 64;
+/// How many columns to either side a trapped gas cell compares when deciding which way to drift.
+const PRESSURE_REACH: usize = 8;
+/// How deep a column of gas is measured when comparing pressure.
+const PRESSURE_DEPTH: usize = 4;
+
+/// How many ticks a fire burns before going out.
+const FIRE_LIFE: u8 = 30;
+/// How many ticks a fire that caught on oil burns before going out.
+const OIL_FIRE_LIFE: u8 = 90;
+/// A fire burning oil gives off smoke on average once every this many ticks.
+const SMOKE_ODDS: u64 = 8;
+/// How many ticks a lit fuse smolders before bursting into flame.
+const FUSE_DELAY: u8 = 4;
+
+/// How many ticks a virus cell lives before dying off.
+const VIRUS_LIFE: u8 = 60;
+/// A virus cell infects a neighbor on average once every this many ticks.
+const VIRUS_ODDS: u64 = 8;
+
+/// Offsets of the four cells that share an edge with a cell.
+const NEIGHBORS: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance)]
+    fn now() -> f64;
+}
+
+/// Measures elapsed time, using the browser's clock when running on the web.
+struct Stopwatch {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    start: f64,
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Stopwatch {
+            #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+            start: now(),
+            #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    fn elapsed_micros(&self) -> f64 {
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+        return (now() - self.start) * 1000.0;
+
+        #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+        return self.start.elapsed().as_secs_f64() * 1_000_000.0;
+    }
+}
+
+/// A JavaScript function to call back into, or, in tests, a log of the calls made.
+enum Callback {
+    #[cfg(feature = "wasm")]
+    Js(js_sys::Function),
+    #[cfg(test)]
+    Log(std::rc::Rc<std::cell::RefCell<Vec<Vec<u32>>>>),
+}
+
+// Exceptions thrown by callbacks have nowhere to go, so they are dropped.
+impl Callback {
+    fn call(&self) {
+        match *self {
+            #[cfg(feature = "wasm")]
+            Callback::Js(ref function) => {
+                let _ = function.call0(&JsValue::NULL);
+            }
+            #[cfg(test)]
+            Callback::Log(ref calls) => calls.borrow_mut().push(Vec::new()),
+        }
+    }
+
+    fn call_with(&self, values: &[u32]) {
+        let _ = values;
+
+        match *self {
+            #[cfg(feature = "wasm")]
+            Callback::Js(ref function) => {
+                let values = js_sys::Uint32Array::from(values);
+
+                let _ = function.call1(&JsValue::NULL, &values);
+            }
+            #[cfg(test)]
+            Callback::Log(ref calls) => calls.borrow_mut().push(values.to_vec()),
+        }
+    }
+}
+
 fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
 
 fn distance(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
-    (((x2 - x1).powi(2) + (y2 - y1).powi(2)) as f32).sqrt()
+    ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt()
 }
 
-#[wasm_bindgen]
+/// Visits every cell on the Bresenham line from `(x1, y1)` to `(x2, y2)`, endpoints included.
+fn bresenham(x1: isize, y1: isize, x2: isize, y2: isize, mut f: impl FnMut(isize, isize)) {
+    let dx = (x2 - x1).abs();
+    let dy = -(y2 - y1).abs();
+    let sx = if x1 < x2 { 1 } else { -1 };
+    let sy = if y1 < y2 { 1 } else { -1 };
+
+    let mut x = x1;
+    let mut y = y1;
+    let mut error = dx + dy;
+
+    loop {
+        f(x, y);
+
+        if x == x2 && y == y2 {
+            break;
+        }
+
+        let doubled = 2 * error;
+
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Visits every cell within a tapered capsule from `(x1, y1)` with radius `r1` to `(x2, y2)` with
+/// radius `r2`.
+#[allow(clippy::too_many_arguments)]
+fn capsule(
+    size: Size,
+    x1: usize,
+    y1: usize,
+    r1: usize,
+    x2: usize,
+    y2: usize,
+    r2: usize,
+    mut f: impl FnMut(usize, usize),
+) {
+    let (ax, ay) = (x1 as f32, y1 as f32);
+    let (bx, by) = (x2 as f32, y2 as f32);
+    let (r1, r2) = (r1 as f32, r2 as f32);
+
+    let reach = r1.max(r2);
+
+    let left = (ax.min(bx) - reach).max(0.0) as usize;
+    let top = (ay.min(by) - reach).max(0.0) as usize;
+    let right = ((ax.max(bx) + reach) as usize + 1).min(size.width);
+    let bottom = ((ay.max(by) + reach) as usize + 1).min(size.height);
+
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+
+    for y in top..bottom {
+        for x in left..right {
+            let (px, py) = (x as f32, y as f32);
+
+            let t = if length == 0.0 {
+                0.0
+            } else {
+                (((px - ax) * dx + (py - ay) * dy) / length).clamp(0.0, 1.0)
+            };
+
+            let radius = r1 + (r2 - r1) * t;
+            let distance = distance(px, py, ax + dx * t, ay + dy * t);
+
+            if distance <= radius {
+                f(x, y);
+            }
+        }
+    }
+}
+
+/// Copies a grid into a grid of a different size, shifted by `(dx, dy)`, setting uncovered cells to
+/// `fill`.
+fn remap<T: Copy>(cells: &[T], from: Size, to: Size, dx: isize, dy: isize, fill: T) -> Vec<T> {
+    let mut result = vec![fill; to.width * to.height];
+
+    for y in 0..from.height {
+        let j = y as isize + dy;
+
+        if j < 0 || j >= to.height as isize {
+            continue;
+        }
+
+        for x in 0..from.width {
+            let i = x as isize + dx;
+
+            if i < 0 || i >= to.width as isize {
+                continue;
+            }
+
+            result[j as usize * to.width + i as usize] = cells[y * from.width + x];
+        }
+    }
+
+    result
+}
+
+/// Visits the center of every disc in a stroke from `(x1, y1)` to `(x2, y2)`, skipping those
+/// further than `reach` from the world.
+fn walk_line(
+    size: Size,
+    reach: usize,
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    mut f: impl FnMut(isize, isize),
+) {
+    let x1 = x1 as isize;
+    let y1 = y1 as isize;
+    let x2 = x2 as isize;
+    let y2 = y2 as isize;
+
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+
+    const LEEWAY: isize = 1;
+
+    if (-LEEWAY..=LEEWAY).contains(&dx) {
+        let range = dy.unsigned_abs();
+        let range = range.max(1);
+
+        let mut x = x1;
+        let mut y = y1;
+
+        if y2 < y1 {
+            x = x2;
+            y = y2;
+        }
+
+        // Clip the rows walked rather than how many there are, so a stroke longer than the world
+        // still covers all of it.
+        let reach = reach as isize;
+        let top = y.max(-reach);
+        let bottom = (y + range as isize).min(size.height as isize + reach);
+
+        for y in top..bottom {
+            f(x, y);
+        }
+
+        return;
+    }
+
+    let slope = dy as f32 / dx as f32;
+    let y_intercept = y1 as f32 - slope * x1 as f32;
+
+    let domain = dx.unsigned_abs();
+    let domain = domain.max(1);
+    let domain = domain.min(size.width);
+
+    let leftmost = x1.min(x2) as f32;
+
+    const STEP: f32 = 0.5;
+    let domain = (domain as f32 / STEP).ceil() as usize;
+
+    for i in 0..domain {
+        let x = leftmost + i as f32 * STEP;
+        let y = (((slope * x).ceil()) + y_intercept) as isize;
+
+        f(x as isize, y);
+    }
+}
+
+/// Visits every cell covered by a stroke of discs of `radius` swept from `(x1, y1)` to `(x2, y2)`,
+/// once each.
+fn stroke(
+    size: Size,
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    radius: usize,
+    mut f: impl FnMut(usize, usize),
+) {
+    let mut centers = Vec::new();
+
+    walk_line(size, radius, x1, y1, x2, y2, |x, y| centers.push((x, y)));
+
+    let radius = radius as isize;
+
+    let left = centers
+        .iter()
+        .map(|&(x, _)| x - radius)
+        .min()
+        .unwrap_or(0)
+        .max(0);
+    let top = centers
+        .iter()
+        .map(|&(_, y)| y - radius)
+        .min()
+        .unwrap_or(0)
+        .max(0);
+    let right = centers
+        .iter()
+        .map(|&(x, _)| x + radius + 1)
+        .max()
+        .unwrap_or(0)
+        .min(size.width as isize);
+    let bottom = centers
+        .iter()
+        .map(|&(_, y)| y + radius + 1)
+        .max()
+        .unwrap_or(0)
+        .min(size.height as isize);
+
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    let span = (right - left) as usize;
+    let mut seen = BitSet::new(span * (bottom - top) as usize);
+
+    for (x, y) in centers {
+        for j in (y - radius).max(top)..(y + radius + 1).min(bottom) {
+            for i in (x - radius).max(left)..(x + radius + 1).min(right) {
+                let distance = distance(x as f32, y as f32, i as f32, j as f32).ceil();
+                let bit = (j - top) as usize * span + (i - left) as usize;
+
+                if distance <= radius as f32 && !seen.get(bit) {
+                    seen.set(bit, true);
+                    f(i as usize, j as usize);
+                }
+            }
+        }
+    }
+}
+
+/// A small xorshift64* generator, so a simulation is reproducible from its seed.
+#[derive(Clone)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Scramble the seed with splitmix64 so that nearby seeds produce unrelated sequences.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        Rng { state: z.max(1) }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns true once every `odds` calls on average.
+    fn chance(&mut self, odds: u64) -> bool {
+        self.next().is_multiple_of(odds.max(1))
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Material {
@@ -28,6 +436,321 @@ pub enum Material {
     Sand = 2,
     Water = 3,
     Smoke = 4,
+    Oil = 5,
+    Acid = 6,
+    Steam = 7,
+    Fire = 8,
+    Fuse = 9,
+    Virus = 10,
+    Ice = 11,
+}
+
+const MATERIAL_COUNT: usize = 12;
+
+/// Spreads that give every material sensible behavior out of the box, indexed by material.
+const DEFAULT_SPREADS: [u8; MATERIAL_COUNT] = [
+    0, // Air
+    0, // Rock
+    1, // Sand
+    5, // Water
+    3, // Smoke
+    4, // Oil
+    3, // Acid
+    3, // Steam
+    0, // Fire
+    0, // Fuse
+    0, // Virus
+    5, // Ice, carried over to the water it melts into
+];
+
+impl TryFrom<u8> for Material {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Material::Air),
+            1 => Ok(Material::Rock),
+            2 => Ok(Material::Sand),
+            3 => Ok(Material::Water),
+            4 => Ok(Material::Smoke),
+            5 => Ok(Material::Oil),
+            6 => Ok(Material::Acid),
+            7 => Ok(Material::Steam),
+            8 => Ok(Material::Fire),
+            9 => Ok(Material::Fuse),
+            10 => Ok(Material::Virus),
+            11 => Ok(Material::Ice),
+            _ => Err(format!("{} is not a valid material", value)),
+        }
+    }
+}
+
+/// What a cell of a material does on its own when it is scanned.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Behavior {
+    /// Stays put unless something else moves it.
+    Static,
+    /// Falls, and piles up by sliding down either side.
+    Powder,
+    /// Falls, and levels out by flowing sideways.
+    Liquid,
+    /// Rises, and drifts sideways under a ceiling.
+    Gas,
+    /// Burns down, lighting anything flammable it touches.
+    Burn,
+    /// Counts down once lit, then bursts into flame.
+    Smolder,
+    /// Spreads into neighboring liquids and gases, then dies off.
+    Infect,
+    /// Melts into water once the world is warm enough.
+    Melt,
+}
+
+/// How a powder, liquid or gas slips past whatever is in its way. Predicates take the moving
+/// material, then the one it is up against.
+struct Movement {
+    /// 1 for materials that fall, -1 for materials that rise.
+    gravity: isize,
+    /// Whether the cell beside it leaves room to slip diagonally past.
+    clears: fn(Material, Material) -> bool,
+    /// Whether it may take the place of the cell diagonally ahead of it.
+    enters: fn(Material, Material) -> bool,
+    /// Whether it may take the place of the cell beside it, or `None` if it never moves sideways.
+    slides: Option<fn(Material, Material) -> bool>,
+}
+
+impl Movement {
+    /// Piles up by sliding down through gases to either side.
+    const POWDER: Movement = Movement {
+        gravity: 1,
+        clears: |material, other| other == material || State::from(other) == State::Gas,
+        enters: |_, other| State::from(other) == State::Gas,
+        slides: None,
+    };
+
+    /// Sinks diagonally into anything lighter, then flows sideways, around fire rather than through
+    /// it.
+    const LIQUID: Movement = Movement {
+        gravity: 1,
+        clears: |material, other| {
+            other == material || (State::from(other) == State::Gas && other != Material::Fire)
+        },
+        enters: |material, other| material.sinks_into(other),
+        slides: Some(|_, other| State::from(other) == State::Gas && other != Material::Fire),
+    };
+
+    /// Creeps along the underside of a ceiling, rising around its edge into air, then drifts
+    /// sideways through air.
+    const GAS: Movement = Movement {
+        gravity: -1,
+        clears: |material, other| other != material && other != Material::Air,
+        enters: |_, other| other == Material::Air,
+        slides: Some(|_, other| other == Material::Air),
+    };
+}
+
+/// Whether a cell tried during a sideways search moved, could go further, or is blocked.
+enum Step {
+    Moved,
+    Open,
+    Blocked,
+}
+
+/// Everything the simulation needs to know about a material.
+struct MaterialProps {
+    state: State,
+    /// Relative weight of the material; heavier liquids sink through lighter ones.
+    density: u8,
+    /// Whether fire sets the material alight.
+    flammable: bool,
+    /// How many ticks a freshly placed cell of the material lasts, if it burns out.
+    life: u8,
+    /// How the material is drawn before any tint, as packed `0xRRGGBBAA`.
+    default_color: u32,
+    behavior: Behavior,
+}
+
+/// The properties of every material, indexed by material.
+const MATERIAL_PROPS: [MaterialProps; MATERIAL_COUNT] = [
+    // Air
+    MaterialProps {
+        state: State::Gas,
+        density: 0,
+        flammable: false,
+        life: 0,
+        default_color: 0x0000_0000,
+        behavior: Behavior::Static,
+    },
+    // Rock
+    MaterialProps {
+        state: State::Solid,
+        density: 20,
+        flammable: false,
+        life: 0,
+        default_color: 0x7a72_6bff,
+        behavior: Behavior::Static,
+    },
+    // Sand
+    MaterialProps {
+        state: State::Solid,
+        density: 16,
+        flammable: false,
+        life: 0,
+        default_color: 0xe3c5_81ff,
+        behavior: Behavior::Powder,
+    },
+    // Water
+    MaterialProps {
+        state: State::Liquid,
+        density: 10,
+        flammable: false,
+        life: 0,
+        default_color: 0x3b7d_d8ff,
+        behavior: Behavior::Liquid,
+    },
+    // Smoke
+    MaterialProps {
+        state: State::Gas,
+        density: 2,
+        flammable: false,
+        life: 0,
+        default_color: 0x5c5c_66b0,
+        behavior: Behavior::Gas,
+    },
+    // Oil
+    MaterialProps {
+        state: State::Liquid,
+        density: 8,
+        flammable: true,
+        life: 0,
+        default_color: 0x3d2f_1eff,
+        behavior: Behavior::Liquid,
+    },
+    // Acid
+    MaterialProps {
+        state: State::Liquid,
+        density: 12,
+        flammable: false,
+        life: 0,
+        default_color: 0x8fe0_3aff,
+        behavior: Behavior::Liquid,
+    },
+    // Steam
+    MaterialProps {
+        state: State::Gas,
+        density: 1,
+        flammable: false,
+        life: 0,
+        default_color: 0xd6dd_e4a0,
+        behavior: Behavior::Gas,
+    },
+    // Fire
+    MaterialProps {
+        state: State::Gas,
+        density: 0,
+        flammable: false,
+        life: FIRE_LIFE,
+        default_color: 0xff7a_1eff,
+        behavior: Behavior::Burn,
+    },
+    // Fuse
+    MaterialProps {
+        state: State::Solid,
+        density: 18,
+        flammable: true,
+        life: 0,
+        default_color: 0x9c6b_3fff,
+        behavior: Behavior::Smolder,
+    },
+    // Virus
+    MaterialProps {
+        state: State::Solid,
+        density: 16,
+        flammable: false,
+        life: VIRUS_LIFE,
+        default_color: 0xb13c_c4ff,
+        behavior: Behavior::Infect,
+    },
+    // Ice
+    MaterialProps {
+        state: State::Solid,
+        density: 9,
+        flammable: false,
+        life: 0,
+        default_color: 0xbfe6_f5ff,
+        behavior: Behavior::Melt,
+    },
+];
+
+impl Material {
+    fn props(self) -> &'static MaterialProps {
+        &MATERIAL_PROPS[self as usize]
+    }
+
+    fn density(self) -> u8 {
+        self.props().density
+    }
+
+    fn life(self) -> u8 {
+        self.props().life
+    }
+
+    fn flammable(self) -> bool {
+        self.props().flammable
+    }
+
+    fn sinks_into(self, other: Material) -> bool {
+        match State::from(other) {
+            State::Gas => true,
+            State::Liquid => self.density() > other.density(),
+            State::Solid => false,
+        }
+    }
+
+    /// Whether a gas rises into `other` directly above it: air, or any denser gas.
+    fn rises_through(self, other: Material) -> bool {
+        match other {
+            Material::Air => true,
+            _ => State::from(other) == State::Gas && other.density() > self.density(),
+        }
+    }
+}
+
+/// A pseudo-random value in `[0, 1)` for every integer lattice point.
+fn lattice(seed: u64, i: i64) -> f32 {
+    let value = Rng::new(seed ^ (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)).next();
+
+    (value >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Smoothly interpolates between lattice values, producing one-dimensional value noise.
+fn value_noise(seed: u64, x: f32) -> f32 {
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3.0 - 2.0 * t);
+
+    let a = lattice(seed, i as i64);
+    let b = lattice(seed, i as i64 + 1);
+
+    a + (b - a) * t
+}
+
+/// Sums octaves of value noise (fractal Brownian motion), normalized to `[0, 1)`.
+fn fbm(seed: u64, x: f32, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut normalization = 0.0;
+
+    for octave in 0..octaves {
+        total += value_noise(seed.wrapping_add(octave as u64), x * frequency) * amplitude;
+        normalization += amplitude;
+
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    total / normalization
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -39,811 +762,6696 @@ enum State {
 
 impl From<Material> for State {
     fn from(material: Material) -> Self {
-        match material {
-            Material::Rock => State::Solid,
-            Material::Sand => State::Solid,
-            Material::Water => State::Liquid,
-            Material::Smoke => State::Gas,
-            Material::Air => State::Gas,
-        }
+        material.props().state
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn is_liquid(material: Material) -> bool {
+    State::from(material) == State::Liquid
+}
+
+/// Air counts as a gas.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn is_gas(material: Material) -> bool {
+    State::from(material) == State::Gas
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn is_flammable(material: Material) -> bool {
+    material.flammable()
+}
+
+/// How a material is drawn before any tint, as packed `0xRRGGBBAA`. Air is transparent.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn material_color(material: Material) -> u32 {
+    material.props().default_color
+}
+
+/// How much darker than its material's base color a cell is drawn, from `None` up to `Deepest`
+/// in even steps. Before there were eight tints, `Dark`, `Darker` and `Darkest` were 1, 2 and 3;
+/// see `tint_from_legacy`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[repr(u8)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Tint {
+    None = 0,
+    Faint = 1,
+    Dark = 2,
+    Shaded = 3,
+    Darker = 4,
+    Deep = 5,
+    Darkest = 6,
+    Deepest = 7,
+}
+
+const TINT_LEVELS: u8 = 8;
+
+impl Tint {
+    /// Every tint, from lightest to darkest.
+    const LEVELS: [Tint; TINT_LEVELS as usize] = [
+        Tint::None,
+        Tint::Faint,
+        Tint::Dark,
+        Tint::Shaded,
+        Tint::Darker,
+        Tint::Deep,
+        Tint::Darkest,
+        Tint::Deepest,
+    ];
+
+    /// Darkens a packed `0xRRGGBBAA` color by this tint, leaving its alpha alone.
+    fn darken(self, rgba: u32) -> u32 {
+        let scale = |shift: u32| {
+            let channel = (rgba >> shift) & 0xff;
+
+            (channel * (TINT_SHADES - self as u32) / TINT_SHADES) << shift
+        };
+
+        scale(24) | scale(16) | scale(8) | (rgba & 0xff)
+    }
+}
+
+/// `Deepest` draws a color at `(TINT_SHADES - 7) / TINT_SHADES` of its brightness.
+const TINT_SHADES: u32 = 16;
+
+impl TryFrom<u8> for Tint {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Tint::LEVELS
+            .get(value as usize)
+            .copied()
+            .ok_or_else(|| format!("{} is not a valid tint", value))
+    }
+}
+
+/// How dark a tint is drawn, from 0 for none up to 7 for the darkest. The same as its value.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn tint_level(tint: Tint) -> u8 {
+    tint as u8
+}
+
+/// Maps one of the four tints from before there were eight, stored as 0 to 3, to the same shade.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn tint_from_legacy(value: u8) -> Result<Tint, String> {
+    match value {
+        0 => Ok(Tint::None),
+        1 => Ok(Tint::Dark),
+        2 => Ok(Tint::Darker),
+        3 => Ok(Tint::Darkest),
+        _ => Err(format!("{} is not a legacy tint", value)),
+    }
+}
+
+/// How a tint is chosen for material placed with the default entry points.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum TintMode {
     None = 0,
     Dark = 1,
     Darker = 2,
     Darkest = 3,
+    /// Picks one of the tints at random for every cell.
+    Random = 4,
+    Faint = 5,
+    Shaded = 6,
+    Deep = 7,
+    Deepest = 8,
 }
 
-#[wasm_bindgen]
+impl TryFrom<u8> for TintMode {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TintMode::None),
+            1 => Ok(TintMode::Dark),
+            2 => Ok(TintMode::Darker),
+            3 => Ok(TintMode::Darkest),
+            4 => Ok(TintMode::Random),
+            5 => Ok(TintMode::Faint),
+            6 => Ok(TintMode::Shaded),
+            7 => Ok(TintMode::Deep),
+            8 => Ok(TintMode::Deepest),
+            _ => Err(format!("{} is not a valid tint mode", value)),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub struct Size {
     pub width: usize,
     pub height: usize,
 }
 
-#[wasm_bindgen]
-pub struct World {
-    size: Size,
-    chunk_size: usize,
-    chunk_columns: usize,
-    hot: bool,
-    active_chunks: Vec<bool>,
-    forecast: Vec<bool>,
-    materials: Vec<Material>,
-    dirty: Vec<bool>,
-    tints: Vec<Tint>,
-    spreads: Vec<u8>,
+/// The order cells are stored in within the buffers returned by `materials`, `tints` and friends.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BufferOrder {
+    /// Row by row from the top of the world, each row from left to right.
+    RowMajor = 0,
 }
 
-#[wasm_bindgen]
-impl World {
-    pub fn create(width: usize, height: usize, chunk_size: usize) -> Self {
-        set_panic_hook();
+/// How the buffers returned by `materials` and `tints` are laid out in memory.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct BufferLayout {
+    /// How many bytes a single cell takes up.
+    pub element_size: usize,
+    /// How many bytes lie between the start of one row and the start of the next.
+    pub row_stride: usize,
+    pub order: BufferOrder,
+}
 
-        let size = Size { width, height };
+/// A summary of a single tick.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct TickReport {
+    /// How many moves were made. A cell that moved more than once is counted every time.
+    pub moved_cells: u32,
+    /// How many chunks were scanned. With substeps, a chunk is counted once for every pass.
+    pub active_chunks: u32,
+    /// Whether anything may still move next tick.
+    pub still_hot: bool,
+}
 
-        let columns = (width as f32 / chunk_size as f32).ceil() as usize;
-        let rows = (height as f32 / chunk_size as f32).ceil() as usize;
+/// Counters from a single call to `simulate`, for tracking down where time goes.
+#[cfg(feature = "profiling")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Default)]
+pub struct SimStats {
+    /// How many moves were made, across every substep.
+    pub moved: usize,
+    /// How many cells were visited by the scan, across every substep.
+    pub cells_scanned: usize,
+    /// How many chunks were scanned, counted once for every substep.
+    pub chunks_active: usize,
+}
 
-        World {
-            size,
-            chunk_size,
-            chunk_columns: columns,
-            hot: false,
-            active_chunks: vec![false; columns * rows],
-            forecast: vec![false; columns * rows],
-            materials: vec![Material::Air; size.width * size.height],
-            tints: vec![Tint::None; size.width * size.height],
-            spreads: vec![0; size.width * size.height],
-            dirty: vec![false; size.width * size.height],
+/// Where the time went during the last call to `simulate`, in microseconds.
+#[cfg(feature = "profiling")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Default)]
+pub struct TickTimings {
+    /// Walking the rows of the active chunks, and skipping past what does not need updating.
+    pub scan_micros: f64,
+    /// Moving and reacting the cells the scan lands on.
+    pub movement_micros: f64,
+    /// Everything around the scan, such as waking chunks and collecting changes.
+    pub bookkeeping_micros: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct TerrainOptions {
+    /// Average height of the ground, in cells from the bottom of the world.
+    pub ground_level: usize,
+    /// How far, in cells, the surface may stray above or below the ground level.
+    pub roughness: usize,
+    /// Height, in cells from the bottom of the world, up to which depressions fill with water.
+    pub water_table: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TerrainOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(ground_level: usize, roughness: usize, water_table: usize) -> Self {
+        TerrainOptions {
+            ground_level,
+            roughness,
+            water_table,
         }
     }
+}
 
-    pub fn size(&self) -> Size {
-        self.size
+/// Ready-made scenes that scale to the size of the world.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum Preset {
+    Hourglass = 0,
+    Waterfall = 1,
+    Volcano = 2,
+    Aquarium = 3,
+}
+
+impl TryFrom<u8> for Preset {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Preset::Hourglass),
+            1 => Ok(Preset::Waterfall),
+            2 => Ok(Preset::Volcano),
+            3 => Ok(Preset::Aquarium),
+            _ => Err(format!("{} is not a valid preset", value)),
+        }
     }
+}
 
-    pub fn materials(&self) -> *const Material {
-        self.materials.as_ptr()
+/// How a tick applies the moves it makes.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// Cells move as soon as they are scanned, and later cells see those moves.
+    InPlace = 0,
+    /// Cells move based on the world as it was at the start of the tick.
+    DoubleBuffered = 1,
+}
+
+impl TryFrom<u8> for UpdateMode {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(UpdateMode::InPlace),
+            1 => Ok(UpdateMode::DoubleBuffered),
+            _ => Err(format!("{} is not a valid update mode", value)),
+        }
+    }
+}
+
+/// Where existing content ends up when a world is resized.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum Anchor {
+    TopLeft = 0,
+    BottomLeft = 1,
+    Center = 2,
+}
+
+impl TryFrom<u8> for Anchor {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Anchor::TopLeft),
+            1 => Ok(Anchor::BottomLeft),
+            2 => Ok(Anchor::Center),
+            _ => Err(format!("{} is not a valid anchor", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Pattern {
+    Checker(usize),
+    Stripes(usize, bool),
+    Mask(u64),
+}
+
+/// Selects which cells of a fill receive material, anchored to world coordinates.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct PatternFill {
+    pattern: Pattern,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PatternFill {
+    /// Alternating squares of `size` by `size` cells.
+    pub fn checker(size: usize) -> Self {
+        PatternFill {
+            pattern: Pattern::Checker(size.max(1)),
+        }
+    }
+
+    /// Alternating bands `size` cells thick.
+    pub fn stripes(size: usize, horizontal: bool) -> Self {
+        PatternFill {
+            pattern: Pattern::Stripes(size.max(1), horizontal),
+        }
     }
 
-    pub fn tints(&self) -> *const Tint {
-        self.tints.as_ptr()
-    }
+    /// An 8x8 tile where bit `y * 8 + x` marks the cell at `(x, y)` as "on".
+    pub fn mask(bits: u64) -> Self {
+        PatternFill {
+            pattern: Pattern::Mask(bits),
+        }
+    }
+}
+
+impl PatternFill {
+    fn write(&self, log: &mut Vec<u8>) {
+        match self.pattern {
+            Pattern::Checker(size) => {
+                log.push(0);
+                write_u32(log, size as u32);
+            }
+            Pattern::Stripes(size, horizontal) => {
+                log.push(1);
+                write_u32(log, size as u32);
+                log.push(horizontal as u8);
+            }
+            Pattern::Mask(bits) => {
+                log.push(2);
+                write_u64(log, bits);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<PatternFill, String> {
+        match reader.u8()? {
+            0 => Ok(PatternFill::checker(reader.usize()?)),
+            1 => Ok(PatternFill::stripes(reader.usize()?, reader.bool()?)),
+            2 => Ok(PatternFill::mask(reader.u64()?)),
+            kind => Err(format!("{} is not a valid pattern", kind)),
+        }
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        match self.pattern {
+            Pattern::Checker(size) => (x / size + y / size).is_multiple_of(2),
+            Pattern::Stripes(size, true) => (y / size).is_multiple_of(2),
+            Pattern::Stripes(size, false) => (x / size).is_multiple_of(2),
+            Pattern::Mask(bits) => bits & (1 << ((y % 8) * 8 + x % 8)) != 0,
+        }
+    }
+}
+
+/// A fixed number of flags, packed 64 to a word.
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    fn new(len: usize) -> BitSet {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns false for anything past the end.
+    fn get(&self, index: usize) -> bool {
+        index < self.len && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Does nothing for anything past the end.
+    fn set(&mut self, index: usize, value: bool) {
+        if index >= self.len {
+            return;
+        }
+
+        if value {
+            self.words[index / 64] |= 1 << (index % 64);
+        } else {
+            self.words[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = 0;
+        }
+    }
+
+    fn fill(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = u64::MAX;
+        }
+
+        // Keep the bits past the end unset, so counting and iterating never see them.
+        if !self.len.is_multiple_of(64) {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << (self.len % 64)) - 1;
+            }
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.words.iter().any(|&word| word != 0)
+    }
+
+    /// Whether any flag from `start` up to, but not including, `end` is set.
+    fn any_in(&self, start: usize, end: usize) -> bool {
+        let end = end.min(self.len);
+
+        if start >= end {
+            return false;
+        }
+
+        let (first, last) = (start / 64, (end - 1) / 64);
+
+        (first..=last).any(|i| {
+            let mut word = self.words[i];
+
+            if i == first {
+                word &= u64::MAX << (start % 64);
+            }
+
+            if i == last && !end.is_multiple_of(64) {
+                word &= (1 << (end % 64)) - 1;
+            }
+
+            word != 0
+        })
+    }
+
+    fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Iterates over the index of every set flag, in order.
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+
+                let bit = word.trailing_zeros() as usize;
+
+                word &= word - 1;
+
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Which cells have already taken part in the current tick, tracked by generation so nothing needs
+/// clearing between ticks.
+struct DirtyCells {
+    generations: Vec<u8>,
+    /// Cells marked with this generation are dirty. Never zero, so fresh cells are clean.
+    current: u8,
+}
+
+impl DirtyCells {
+    fn new(len: usize) -> DirtyCells {
+        DirtyCells {
+            generations: vec![0; len],
+            current: 1,
+        }
+    }
+
+    fn get(&self, index: usize) -> bool {
+        self.generations[index] == self.current
+    }
+
+    fn mark(&mut self, index: usize) {
+        self.generations[index] = self.current;
+    }
+
+    /// Makes every cell clean again.
+    fn clear(&mut self) {
+        self.current = self.current.wrapping_add(1);
+
+        // Once the counter wraps around, old marks would come back to life, so wipe them.
+        if self.current == 0 {
+            for generation in self.generations.iter_mut() {
+                *generation = 0;
+            }
+
+            self.current = 1;
+        }
+    }
+
+    #[cfg(feature = "debug-checks")]
+    fn first(&self) -> Option<usize> {
+        self.generations
+            .iter()
+            .position(|&generation| generation == self.current)
+    }
+}
+
+/// An inclusive rectangle of cells within a chunk that may need updating.
+#[derive(Clone, Copy)]
+struct Bounds {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl Bounds {
+    const EMPTY: Bounds = Bounds {
+        left: usize::MAX,
+        top: usize::MAX,
+        right: 0,
+        bottom: 0,
+    };
+
+    const ALL: Bounds = Bounds {
+        left: 0,
+        top: 0,
+        right: usize::MAX,
+        bottom: usize::MAX,
+    };
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.left && x <= self.right && y >= self.top && y <= self.bottom
+    }
+
+    fn is_empty(&self) -> bool {
+        self.left > self.right || self.top > self.bottom
+    }
+
+    fn include(&mut self, other: Bounds) {
+        self.left = self.left.min(other.left);
+        self.top = self.top.min(other.top);
+        self.right = self.right.max(other.right);
+        self.bottom = self.bottom.max(other.bottom);
+    }
+
+    fn intersect(&self, other: Bounds) -> Bounds {
+        Bounds {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    /// The same cells, in a world whose first row is `rows` further down.
+    fn raised(&self, rows: usize) -> Bounds {
+        Bounds {
+            top: self.top.saturating_sub(rows),
+            bottom: self.bottom.saturating_sub(rows),
+            ..*self
+        }
+    }
+
+    /// The same cells, in a world whose first row is `rows` further up.
+    fn lowered(&self, rows: usize) -> Bounds {
+        Bounds {
+            top: self.top.saturating_add(rows),
+            bottom: self.bottom.saturating_add(rows),
+            ..*self
+        }
+    }
+}
+
+/// A chunk along with every chunk around it that lies inside the world.
+#[derive(Clone, Copy)]
+struct Neighborhood {
+    chunks: [u32; 9],
+    len: u8,
+}
+
+impl Neighborhood {
+    /// Works out the neighborhood of every chunk in a grid of `columns` by `rows` chunks.
+    fn all(columns: usize, rows: usize) -> Vec<Neighborhood> {
+        let mut neighborhoods = Vec::with_capacity(columns * rows);
+
+        for cy in 0..rows {
+            for cx in 0..columns {
+                let mut neighborhood = Neighborhood {
+                    chunks: [0; 9],
+                    len: 0,
+                };
+
+                for y in cy.saturating_sub(1)..(cy + 2).min(rows) {
+                    for x in cx.saturating_sub(1)..(cx + 2).min(columns) {
+                        neighborhood.chunks[neighborhood.len as usize] = (y * columns + x) as u32;
+                        neighborhood.len += 1;
+                    }
+                }
+
+                neighborhoods.push(neighborhood);
+            }
+        }
+
+        neighborhoods
+    }
+
+    fn chunks(&self) -> &[u32] {
+        &self.chunks[..self.len as usize]
+    }
+}
+
+/// A tick that has started but not finished, over the chunks from `(left, top)` to `(right,
+/// bottom)`, exclusive.
+#[derive(Clone, Copy)]
+struct Tick {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    /// The next row to process, counted in scan order.
+    row: usize,
+}
+
+/// A stripe of a striped tick, split off onto a world of its own. See `World::set_stripe_rows`.
+struct Stripe {
+    world: World,
+    /// The row of the world the stripe was split from that the stripe's world starts at.
+    offset: usize,
+    /// How much of each material the stripe's world held before it ran.
+    counts: [u32; MATERIAL_COUNT],
+}
+
+// SAFETY: a stripe's world never has callbacks, the only part of a world that cannot leave the
+// thread it was made on.
+unsafe impl Send for Stripe {}
+
+impl Stripe {
+    fn run(&mut self) {
+        let rows = self.world.window.bottom - self.world.window.top + 1;
+
+        for row in 0..rows {
+            self.world.process_row(row);
+        }
+    }
+}
+
+/// The largest world, in cells, that `export_csv` will write out.
+const CSV_CELL_LIMIT: usize = 256 * 256;
+
+/// What `get_many` returns for an index outside the world. No material has this discriminant.
+const OUT_OF_RANGE: u8 = 255;
+
+/// The largest world, in cells, that `replay` will build.
+const REPLAY_CELL_LIMIT: usize = 1 << 24;
+
+/// Identifies a recording made by `stop_recording`.
+const RECORDING_MAGIC: &[u8; 4] = b"SAND";
+const RECORDING_VERSION: u8 = 2;
+
+/// The operations a recording can hold, stored as a tag byte followed by their arguments.
+const OP_PLACE: u8 = 0;
+const OP_PAINT: u8 = 1;
+const OP_SIMULATE: u8 = 2;
+const OP_PLACE_WITH_TEMP: u8 = 3;
+const OP_RESET: u8 = 4;
+const OP_RESET_REGION: u8 = 5;
+const OP_SET_EROSION: u8 = 6;
+const OP_PLACE_DEFAULT: u8 = 7;
+const OP_PLACE_NATURAL: u8 = 8;
+const OP_PAINT_TAPERED: u8 = 9;
+const OP_PAINT_DEFAULT: u8 = 10;
+const OP_PAINT_MIXED: u8 = 11;
+const OP_FILL_RECT: u8 = 12;
+const OP_FILL_RECT_PATTERN: u8 = 13;
+const OP_PLACE_POLYGON: u8 = 14;
+const OP_PLACE_POLYGON_PATTERN: u8 = 15;
+const OP_STAMP_MASK: u8 = 16;
+const OP_SCATTER: u8 = 17;
+const OP_FILL_AIR: u8 = 18;
+const OP_INVERT_REGION: u8 = 19;
+const OP_PASTE_REGION: u8 = 20;
+const OP_SET_TINT_RECT: u8 = 21;
+const OP_SET_SPREAD_RECT: u8 = 22;
+const OP_SET_SPREAD_GLOBAL: u8 = 23;
+const OP_SET_CELL_COLOR_OVERRIDE: u8 = 24;
+const OP_CLEAR_CELL_COLOR_OVERRIDE: u8 = 25;
+/// Followed by the snapshot that `undo` or `redo` put back.
+const OP_RESTORE: u8 = 26;
+/// Followed by the tick count, generator state and snapshot that `rewind` went back to.
+const OP_REWIND: u8 = 27;
+const OP_RESIZE: u8 = 28;
+const OP_REBUILD_CHUNKS: u8 = 29;
+const OP_SHRINK_TO_WORLD: u8 = 30;
+const OP_WARM_ALL: u8 = 31;
+const OP_SIMULATE_REGION: u8 = 32;
+/// Followed by how many rows the call got through before running out of time.
+const OP_SIMULATE_BUDGETED: u8 = 33;
+const OP_DEBUG_STEP: u8 = 34;
+const OP_SET_DIRTY_RECTS: u8 = 35;
+const OP_SET_MAX_FALL_SPEED: u8 = 36;
+const OP_SET_MAX_SPREAD_PER_TICK: u8 = 37;
+const OP_SET_FLOW_BIAS: u8 = 38;
+const OP_SET_SIMULATION_BOUNDS: u8 = 39;
+const OP_CLEAR_SIMULATION_BOUNDS: u8 = 40;
+const OP_SET_SUBSTEPS: u8 = 41;
+const OP_SET_TICK_RATE_DIVISOR: u8 = 42;
+const OP_SET_MATERIAL_PAUSED: u8 = 43;
+const OP_SET_DEFAULT_SPREAD: u8 = 44;
+const OP_SET_DEFAULT_TINT_MODE: u8 = 45;
+const OP_SET_UPDATE_MODE: u8 = 46;
+const OP_SET_SEED: u8 = 47;
+const OP_SET_AMBIENT_TEMPERATURE: u8 = 48;
+const OP_SET_EVAPORATION: u8 = 49;
+const OP_SET_SURFACE_TENSION: u8 = 50;
+const OP_SET_PARTICLE_LIMIT: u8 = 51;
+const OP_SET_MAX_ACTIVE_CHUNKS: u8 = 52;
+const OP_LOAD_PRESET: u8 = 53;
+const OP_GENERATE_TERRAIN: u8 = 54;
+const OP_GENERATE_CAVES: u8 = 55;
+const OP_SET_STRIPE_ROWS: u8 = 56;
+
+/// Fails unless a world of `width` by `height` cells fits within `REPLAY_CELL_LIMIT`.
+fn check_replay_size(width: usize, height: usize) -> Result<(), String> {
+    match width.checked_mul(height) {
+        Some(cells) if cells <= REPLAY_CELL_LIMIT => Ok(()),
+        _ => Err(format!(
+            "a {}x{} world is too large to replay",
+            width, height
+        )),
+    }
+}
+
+fn write_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Writes a `u32` length followed by that many bytes.
+fn write_list(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(buffer, bytes.len() as u32);
+    buffer.extend_from_slice(bytes);
+}
+
+fn write_points(buffer: &mut Vec<u8>, points: &[u32]) {
+    write_u32(buffer, points.len() as u32);
+
+    for &point in points {
+        write_u32(buffer, point);
+    }
+}
+
+fn read_points(reader: &mut Reader) -> Result<Vec<u32>, String> {
+    (0..reader.usize()?).map(|_| reader.u32()).collect()
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Folds bytes into a 64-bit FNV-1a hash.
+fn fnv1a(hash: u64, bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Reads little-endian values from a recording, failing instead of panicking when it runs out.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.offset.saturating_add(count);
+
+        if end > self.data.len() {
+            return Err("the recording ended unexpectedly".to_string());
+        }
+
+        let bytes = &self.data[self.offset..end];
+
+        self.offset = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        let bytes = self.bytes(2)?;
+
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn material(&mut self) -> Result<Material, String> {
+        Material::try_from(self.u8()?)
+    }
+
+    fn tint(&mut self) -> Result<Tint, String> {
+        Tint::try_from(self.u8()?)
+    }
+
+    /// Reads a `u32` length followed by that many bytes.
+    fn list(&mut self) -> Result<&'a [u8], String> {
+        let len = self.usize()?;
+
+        self.bytes(len)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+}
+
+/// What happened while processing part of a tick, collected by `debug_step`.
+#[derive(Default)]
+struct DebugLog {
+    visited: Vec<u32>,
+    swaps: Vec<u32>,
+}
+
+/// Everything `debug_step` saw while processing a row of chunks.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct DebugStepInfo {
+    chunk_row: usize,
+    visited: Vec<u32>,
+    swaps: Vec<u32>,
+    complete: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DebugStepInfo {
+    /// The row of chunks that was processed.
+    pub fn chunk_row(&self) -> usize {
+        self.chunk_row
+    }
+
+    /// Linear indices of the cells that were updated, in the order they were visited.
+    pub fn visited(&self) -> Vec<u32> {
+        self.visited.clone()
+    }
+
+    /// Pairs of linear indices of cells that swapped places, in the order they swapped.
+    pub fn swaps(&self) -> Vec<u32> {
+        self.swaps.clone()
+    }
+
+    /// Whether this step finished the tick.
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// A copy of every per-cell channel of a world.
+struct Snapshot {
+    materials: Vec<Material>,
+    tints: Vec<Tint>,
+    spreads: Vec<u8>,
+    lives: Vec<u8>,
+    velocities: Vec<u8>,
+    temperatures: Vec<i16>,
+}
+
+impl Snapshot {
+    /// Appends every channel to a recording, one after the other.
+    fn write(&self, log: &mut Vec<u8>) {
+        log.extend(self.materials.iter().map(|&material| material as u8));
+        log.extend(self.tints.iter().map(|&tint| tint as u8));
+        log.extend_from_slice(&self.spreads);
+        log.extend_from_slice(&self.lives);
+        log.extend_from_slice(&self.velocities);
+
+        for temperature in self.temperatures.iter() {
+            log.extend_from_slice(&temperature.to_le_bytes());
+        }
+    }
+
+    /// Reads back what `write` appended for a world of `cells` cells.
+    fn read(reader: &mut Reader, cells: usize) -> Result<Snapshot, String> {
+        Ok(Snapshot {
+            materials: reader
+                .bytes(cells)?
+                .iter()
+                .map(|&material| Material::try_from(material))
+                .collect::<Result<Vec<_>, _>>()?,
+            tints: reader
+                .bytes(cells)?
+                .iter()
+                .map(|&tint| Tint::try_from(tint))
+                .collect::<Result<Vec<_>, _>>()?,
+            spreads: reader.bytes(cells)?.to_vec(),
+            lives: reader.bytes(cells)?.to_vec(),
+            velocities: reader.bytes(cells)?.to_vec(),
+            temperatures: reader
+                .bytes(2 * cells)?
+                .chunks_exact(2)
+                .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+                .collect(),
+        })
+    }
+
+    /// Packs every channel into one run-length encoded buffer.
+    fn compress(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(7 * self.materials.len());
+
+        bytes.extend(self.materials.iter().map(|&material| material as u8));
+        bytes.extend(self.tints.iter().map(|&tint| tint as u8));
+        bytes.extend_from_slice(&self.spreads);
+        bytes.extend_from_slice(&self.lives);
+        bytes.extend_from_slice(&self.velocities);
+
+        // Low and high bytes are kept apart, since interleaving them would break up every run.
+        bytes.extend(
+            self.temperatures
+                .iter()
+                .map(|temperature| temperature.to_le_bytes()[0]),
+        );
+        bytes.extend(
+            self.temperatures
+                .iter()
+                .map(|temperature| temperature.to_le_bytes()[1]),
+        );
+
+        let mut packed = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let value = bytes[i];
+            let mut run = 1;
+
+            while run < u8::MAX as usize && i + run < bytes.len() && bytes[i + run] == value {
+                run += 1;
+            }
+
+            packed.push(run as u8);
+            packed.push(value);
+
+            i += run;
+        }
+
+        packed
+    }
+
+    /// Unpacks a buffer made by `compress` for a world of `cells` cells.
+    fn decompress(packed: &[u8], cells: usize) -> Snapshot {
+        let mut bytes = Vec::with_capacity(7 * cells);
+
+        for pair in packed.chunks_exact(2) {
+            bytes.extend(std::iter::repeat_n(pair[1], pair[0] as usize));
+        }
+
+        let (materials, rest) = bytes.split_at(cells);
+        let (tints, rest) = rest.split_at(cells);
+        let (spreads, rest) = rest.split_at(cells);
+        let (lives, rest) = rest.split_at(cells);
+        let (velocities, rest) = rest.split_at(cells);
+        let (low, high) = rest.split_at(cells);
+
+        Snapshot {
+            materials: materials
+                .iter()
+                .map(|&material| Material::try_from(material).unwrap_or(Material::Air))
+                .collect(),
+            tints: tints
+                .iter()
+                .map(|&tint| Tint::try_from(tint).unwrap_or(Tint::None))
+                .collect(),
+            spreads: spreads.to_vec(),
+            lives: lives.to_vec(),
+            velocities: velocities.to_vec(),
+            temperatures: low
+                .iter()
+                .zip(high.iter())
+                .map(|(&low, &high)| i16::from_le_bytes([low, high]))
+                .collect(),
+        }
+    }
+}
+
+/// Snapshots of the world taken before each edit, so edits can be undone and redone.
+struct History {
+    capacity: usize,
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// How many edits are in progress. Nested edits are folded into the outermost one.
+    depth: usize,
+    pending: Option<Snapshot>,
+    /// Whether the edit in progress has written to any cell.
+    touched: bool,
+}
+
+/// Compressed snapshots of the world taken every so many ticks, for `rewind`.
+struct Rewind {
+    capacity: usize,
+    interval: u16,
+    /// Ticks since the last snapshot was taken.
+    elapsed: u16,
+    frames: VecDeque<RewindFrame>,
+}
+
+struct RewindFrame {
+    ticks: u64,
+    /// The random number generator is wound back too, so the world plays out the same way again.
+    rng: u64,
+    packed: Vec<u8>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct World {
+    size: Size,
+    chunk_size: usize,
+    chunk_columns: usize,
+    chunk_rows: usize,
+    /// The chunks that wake up alongside each chunk, indexed by chunk.
+    neighborhoods: Vec<Neighborhood>,
+    hot: bool,
+    active_chunks: BitSet,
+    forecast: BitSet,
+    /// Chunks that settled along the inside edge of a region passed to `simulate_region`, woken
+    /// once a tick covers anything else.
+    fenced: BitSet,
+    activity: Vec<u8>,
+    /// Chunks scanned or changed that have yet to make it onto the chunk list.
+    touched_chunks: Vec<bool>,
+    listed_chunks: Vec<bool>,
+    chunk_list: Vec<u32>,
+    /// Chunks holding a cell that changed since the last call to `changed_rects`.
+    changed_chunks: BitSet,
+    /// The most chunks a single tick scans, set by `set_max_active_chunks`.
+    max_active_chunks: u32,
+    /// How many ticks in a row each chunk has been held back by the cap on active chunks.
+    deferrals: Vec<u32>,
+    /// How many chunk rows each stripe of a striped tick covers, or 0 if ticks are not striped.
+    stripe_rows: usize,
+    /// Moves made in each chunk during the last tick. Empty unless chunk stats are enabled.
+    chunk_stats: Vec<u16>,
+    dirty_rects: bool,
+    active_bounds: Vec<Bounds>,
+    forecast_bounds: Vec<Bounds>,
+    max_spread: u8,
+    /// How far any cell may spread in a single tick, whatever its own spread.
+    spread_cap: u8,
+    /// Which way, and how strongly, liquids prefer to flow sideways, set by `set_flow_bias`.
+    flow_bias: i8,
+    /// The cells every tick is confined to, set by `set_simulation_bounds`.
+    simulation_bounds: Bounds,
+    window: Bounds,
+    tick: Option<Tick>,
+    ticks: u64,
+    debug_log: Option<DebugLog>,
+    recording: Option<Vec<u8>>,
+    materials: Vec<Material>,
+    /// How many cells hold each material, kept up to date with every change.
+    counts: [u32; MATERIAL_COUNT],
+    /// How many cells in each row hold something other than air, kept up to date the same way.
+    row_counts: Vec<u32>,
+    particle_limit: u32,
+    dirty: DirtyCells,
+    changes: Vec<u32>,
+    changed: Vec<bool>,
+    /// Every cell packed into one value, brought up to date by `cells_ptr`.
+    cells: Vec<u16>,
+    /// Cells changed since `cells` was last brought up to date, unless it needs rebuilding anyway.
+    cells_pending: Vec<u32>,
+    cells_rebuild: bool,
+    /// Every cell's color as `RGBA` bytes, redrawn by `render_rgba`.
+    rgba: Vec<u8>,
+    changes_stale: bool,
+    moved: u32,
+    /// How many distinct cells, other than air, moved during the last tick.
+    active_cells: u32,
+    /// Which cells moved during the last tick.
+    motion: Vec<bool>,
+    /// Every cell flagged in `motion`, so the flags can be cleared without touching the rest.
+    moving: Vec<u32>,
+    tints: Vec<Tint>,
+    spreads: Vec<u8>,
+    lives: Vec<u8>,
+    velocities: Vec<u8>,
+    /// The temperature of every cell, in degrees Celsius. It moves along with the cell.
+    temperatures: Vec<i16>,
+    /// Custom colors for individual cells, as packed RGBA, taking precedence over palette and tint.
+    color_overrides: HashMap<usize, u32>,
+    rng: Rng,
+    ambient_temperature: i16,
+    evaporation: bool,
+    erosion: bool,
+    surface_tension: bool,
+    update_mode: UpdateMode,
+    front: Vec<Material>,
+    default_spreads: [u8; MATERIAL_COUNT],
+    default_tints: [TintMode; MATERIAL_COUNT],
+    max_fall_speed: u8,
+    substeps: u8,
+    /// Material only moves on ticks that are a multiple of this.
+    tick_rate_divisor: u8,
+    paused: [bool; MATERIAL_COUNT],
+    history: Option<History>,
+    rewind: Option<Rewind>,
+    idle_callback: Option<Callback>,
+    #[cfg(feature = "profiling")]
+    scanned: usize,
+    #[cfg(feature = "profiling")]
+    stats: SimStats,
+    #[cfg(feature = "profiling")]
+    timings: TickTimings,
+    /// Chunks that something other than air moved into during the last tick.
+    #[cfg(feature = "debug-checks")]
+    moved_chunks: Vec<bool>,
+    /// The chunks covered by the last tick, as `(left, top, right, bottom)`.
+    #[cfg(feature = "debug-checks")]
+    last_region: (usize, usize, usize, usize),
+    cell_callbacks: [Option<Callback>; MATERIAL_COUNT],
+    /// The materials as of the last batch of cell events. Empty unless a cell callback is set.
+    watched: Vec<Material>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl World {
+    pub fn create(width: usize, height: usize, chunk_size: usize) -> Self {
+        set_panic_hook();
+
+        let size = Size { width, height };
+
+        let columns = (width as f32 / chunk_size as f32).ceil() as usize;
+        let rows = (height as f32 / chunk_size as f32).ceil() as usize;
+
+        World {
+            size,
+            chunk_size,
+            chunk_columns: columns,
+            chunk_rows: rows,
+            neighborhoods: Neighborhood::all(columns, rows),
+            hot: false,
+            active_chunks: BitSet::new(columns * rows),
+            forecast: BitSet::new(columns * rows),
+            fenced: BitSet::new(columns * rows),
+            activity: vec![0; columns * rows],
+            touched_chunks: vec![false; columns * rows],
+            listed_chunks: vec![false; columns * rows],
+            chunk_list: Vec::new(),
+            changed_chunks: BitSet::new(columns * rows),
+            max_active_chunks: u32::MAX,
+            deferrals: vec![0; columns * rows],
+            stripe_rows: 0,
+            chunk_stats: Vec::new(),
+            dirty_rects: false,
+            active_bounds: vec![Bounds::EMPTY; columns * rows],
+            forecast_bounds: vec![Bounds::EMPTY; columns * rows],
+            max_spread: 0,
+            spread_cap: u8::MAX,
+            flow_bias: 0,
+            simulation_bounds: Bounds::ALL,
+            window: Bounds::ALL,
+            tick: None,
+            ticks: 0,
+            debug_log: None,
+            recording: None,
+            materials: vec![Material::Air; size.width * size.height],
+            counts: {
+                let mut counts = [0; MATERIAL_COUNT];
+                counts[Material::Air as usize] = (size.width * size.height) as u32;
+                counts
+            },
+            row_counts: vec![0; size.height],
+            particle_limit: u32::MAX,
+            tints: vec![Tint::None; size.width * size.height],
+            spreads: vec![0; size.width * size.height],
+            lives: vec![0; size.width * size.height],
+            velocities: vec![0; size.width * size.height],
+            temperatures: vec![DEFAULT_AMBIENT_TEMPERATURE; size.width * size.height],
+            color_overrides: HashMap::new(),
+            dirty: DirtyCells::new(size.width * size.height),
+            changes: Vec::new(),
+            cells: Vec::new(),
+            cells_pending: Vec::new(),
+            cells_rebuild: true,
+            rgba: Vec::new(),
+            changed: vec![false; size.width * size.height],
+            motion: vec![false; size.width * size.height],
+            moving: Vec::new(),
+            changes_stale: false,
+            moved: 0,
+            active_cells: 0,
+            rng: Rng::new(0),
+            ambient_temperature: DEFAULT_AMBIENT_TEMPERATURE,
+            evaporation: false,
+            erosion: false,
+            surface_tension: false,
+            update_mode: UpdateMode::InPlace,
+            front: Vec::new(),
+            default_spreads: DEFAULT_SPREADS,
+            default_tints: [TintMode::None; MATERIAL_COUNT],
+            max_fall_speed: 1,
+            substeps: 1,
+            tick_rate_divisor: 1,
+            paused: [false; MATERIAL_COUNT],
+            history: None,
+            rewind: None,
+            idle_callback: None,
+            #[cfg(feature = "profiling")]
+            scanned: 0,
+            #[cfg(feature = "profiling")]
+            stats: SimStats::default(),
+            #[cfg(feature = "profiling")]
+            timings: TickTimings::default(),
+            #[cfg(feature = "debug-checks")]
+            moved_chunks: vec![false; columns * rows],
+            #[cfg(feature = "debug-checks")]
+            last_region: (0, 0, columns, rows),
+            cell_callbacks: Default::default(),
+            watched: Vec::new(),
+        }
+    }
+
+    /// Narrows each active chunk down to the cells around recent changes, rather than rescanning it
+    /// whole.
+    pub fn set_dirty_rects(&mut self, enabled: bool) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_DIRTY_RECTS, enabled as u8]);
+        }
+
+        self.dirty_rects = enabled;
+
+        for entry in self.forecast_bounds.iter_mut() {
+            *entry = Bounds::EMPTY;
+        }
+
+        self.warm_all_chunks();
+    }
+
+    /// Keeps up to `capacity` snapshots from before each edit for `undo` and `redo`. Zero turns
+    /// history off.
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history = if capacity == 0 {
+            None
+        } else {
+            Some(History {
+                capacity,
+                undo: VecDeque::new(),
+                redo: Vec::new(),
+                depth: 0,
+                pending: None,
+                touched: false,
+            })
+        };
+    }
+
+    /// Reverts the most recent edit. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let snapshot = match self
+            .history
+            .as_mut()
+            .and_then(|history| history.undo.pop_back())
+        {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        let current = self.snapshot();
+
+        if let Some(history) = self.history.as_mut() {
+            history.redo.push(current);
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_RESTORE);
+            snapshot.write(log);
+        }
+
+        self.restore(snapshot);
+
+        true
+    }
+
+    /// Reapplies the most recently undone edit. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let snapshot = match self.history.as_mut().and_then(|history| history.redo.pop()) {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        let current = self.snapshot();
+
+        if let Some(history) = self.history.as_mut() {
+            history.undo.push_back(current);
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_RESTORE);
+            snapshot.write(log);
+        }
+
+        self.restore(snapshot);
+
+        true
+    }
+
+    /// Snapshots the world every `interval_ticks` ticks, keeping the `capacity` most recent for
+    /// `rewind`. Zero turns rewinding off.
+    pub fn enable_rewind(&mut self, capacity: u8, interval_ticks: u16) {
+        self.rewind = if capacity == 0 {
+            None
+        } else {
+            Some(Rewind {
+                capacity: capacity as usize,
+                interval: interval_ticks.max(1),
+                elapsed: 0,
+                frames: VecDeque::new(),
+            })
+        };
+    }
+
+    /// Winds the world back `steps` snapshots, or as far as possible, discarding every snapshot
+    /// after it. Returns false if there is nothing to go back to.
+    pub fn rewind(&mut self, steps: u8) -> bool {
+        let rewind = match self.rewind.as_mut() {
+            Some(rewind) if steps > 0 && !rewind.frames.is_empty() => rewind,
+            _ => return false,
+        };
+
+        let keep = rewind
+            .frames
+            .len()
+            .saturating_sub(steps as usize - 1)
+            .max(1);
+
+        rewind.frames.truncate(keep);
+        rewind.elapsed = 0;
+
+        let frame = match rewind.frames.pop_back() {
+            Some(frame) => frame,
+            None => return false,
+        };
+
+        // An unfinished tick would carry on from rows that no longer match the world.
+        self.finish_tick();
+
+        let snapshot = Snapshot::decompress(&frame.packed, self.materials.len());
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_REWIND);
+            write_u64(log, frame.ticks);
+            write_u64(log, frame.rng);
+            snapshot.write(log);
+        }
+
+        self.restore(snapshot);
+        self.ticks = frame.ticks;
+        self.rng.state = frame.rng;
+
+        // The snapshot just restored is the world as it is now, so it stays the most recent one.
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.frames.push_back(frame);
+        }
+
+        true
+    }
+
+    /// Starts logging every edit, setting and tick, so the session can be reproduced with `replay`.
+    pub fn start_recording(&mut self) {
+        // Start from a clean slate that a fresh world can reproduce exactly.
+        self.finish_tick();
+
+        self.forecast.clear();
+
+        for entry in self.forecast_bounds.iter_mut() {
+            *entry = Bounds::EMPTY;
+        }
+
+        for entry in self.deferrals.iter_mut() {
+            *entry = 0;
+        }
+
+        self.warm_all_chunks();
+
+        let mut log = Vec::new();
+
+        log.extend_from_slice(RECORDING_MAGIC);
+        log.push(RECORDING_VERSION);
+
+        write_u32(&mut log, self.size.width as u32);
+        write_u32(&mut log, self.size.height as u32);
+        write_u32(&mut log, self.chunk_size as u32);
+        write_u64(&mut log, self.rng.state);
+        write_u64(&mut log, self.ticks);
+
+        log.extend_from_slice(&self.ambient_temperature.to_le_bytes());
+        log.push(self.evaporation as u8);
+        log.push(self.update_mode as u8);
+        log.push(self.dirty_rects as u8);
+        log.push(self.max_fall_speed);
+        log.push(self.substeps);
+        log.push(self.max_spread);
+        log.extend(self.paused.iter().map(|&paused| paused as u8));
+        write_u32(&mut log, self.particle_limit);
+        log.push(self.spread_cap);
+
+        write_u64(&mut log, self.simulation_bounds.left as u64);
+        write_u64(&mut log, self.simulation_bounds.top as u64);
+        write_u64(&mut log, self.simulation_bounds.right as u64);
+        write_u64(&mut log, self.simulation_bounds.bottom as u64);
+        log.push(self.tick_rate_divisor);
+        log.push(self.flow_bias as u8);
+        write_u32(&mut log, self.max_active_chunks);
+        log.push(self.surface_tension as u8);
+        log.push(self.erosion as u8);
+        log.extend_from_slice(&self.default_spreads);
+        log.extend(self.default_tints.iter().map(|&mode| mode as u8));
+        write_u32(&mut log, self.stripe_rows as u32);
+
+        self.snapshot().write(&mut log);
+
+        self.recording = Some(log);
+    }
+
+    /// Stops recording and returns the log, or nothing if no recording was in progress.
+    pub fn stop_recording(&mut self) -> Vec<u8> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Logs an operation if a recording is in progress, holding the log back until
+    /// `resume_recording` so nested calls are not logged twice.
+    fn record(&mut self, op: u8, write: impl FnOnce(&mut Vec<u8>)) -> Option<Vec<u8>> {
+        let mut log = self.recording.take()?;
+
+        log.push(op);
+        write(&mut log);
+
+        Some(log)
+    }
+
+    fn resume_recording(&mut self, log: Option<Vec<u8>>) {
+        if log.is_some() {
+            self.recording = log;
+        }
+    }
+
+    /// Same as `resume_recording`, after logging the `state_hash` the operation left behind.
+    fn resume_recording_with_hash(&mut self, mut log: Option<Vec<u8>>) {
+        if let Some(log) = log.as_mut() {
+            write_u64(log, self.state_hash());
+        }
+
+        self.resume_recording(log);
+    }
+
+    /// Rebuilds a world from a log made by `stop_recording`. Fails if any tick ends on a different
+    /// `state_hash` than it did while recording.
+    pub fn replay(log: &[u8]) -> Result<World, String> {
+        let mut reader = Reader {
+            data: log,
+            offset: 0,
+        };
+
+        if reader.bytes(4)? != RECORDING_MAGIC {
+            return Err("this is not a recording".to_string());
+        }
+
+        let version = reader.u8()?;
+
+        if version != RECORDING_VERSION {
+            return Err(format!("unsupported recording version {}", version));
+        }
+
+        let width = reader.usize()?;
+        let height = reader.usize()?;
+        let chunk_size = reader.usize()?;
+
+        if width == 0 || height == 0 {
+            return Err(format!("a {}x{} world is empty", width, height));
+        }
+
+        if chunk_size == 0 || chunk_size > width.max(height) {
+            return Err(format!(
+                "a chunk size of {} does not fit a {}x{} world",
+                chunk_size, width, height
+            ));
+        }
+
+        check_replay_size(width, height)?;
+
+        let mut world = World::create(width, height, chunk_size);
+
+        world.rng.state = reader.u64()?;
+        world.ticks = reader.u64()?;
+
+        world.ambient_temperature = reader.i16()?;
+        world.evaporation = reader.bool()?;
+        world.update_mode = UpdateMode::try_from(reader.u8()?)?;
+        world.dirty_rects = reader.bool()?;
+        world.max_fall_speed = reader.u8()?;
+        world.substeps = reader.u8()?;
+        world.max_spread = reader.u8()?;
+
+        for paused in world.paused.iter_mut() {
+            *paused = reader.bool()?;
+        }
+
+        world.particle_limit = reader.u32()?;
+        world.spread_cap = reader.u8()?;
+        world.simulation_bounds = Bounds {
+            left: reader.u64()? as usize,
+            top: reader.u64()? as usize,
+            right: reader.u64()? as usize,
+            bottom: reader.u64()? as usize,
+        };
+        world.tick_rate_divisor = reader.u8()?.max(1);
+        world.flow_bias = reader.u8()? as i8;
+        world.max_active_chunks = reader.u32()?.max(1);
+        world.surface_tension = reader.bool()?;
+        world.erosion = reader.bool()?;
+
+        for spread in world.default_spreads.iter_mut() {
+            *spread = reader.u8()?;
+        }
+
+        for mode in world.default_tints.iter_mut() {
+            *mode = TintMode::try_from(reader.u8()?)?;
+        }
+
+        world.set_stripe_rows(reader.u32()?);
+
+        let snapshot = Snapshot::read(&mut reader, width * height)?;
+
+        world.restore(snapshot);
+
+        // Every tick is followed by the hash it ended on, to catch the replay drifting off course.
+        let check = |reader: &mut Reader, world: &World| {
+            if reader.u64()? == world.state_hash() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "the replay diverged from the recording on tick {}",
+                    world.ticks
+                ))
+            }
+        };
+
+        while !reader.is_empty() {
+            match reader.u8()? {
+                OP_PLACE => {
+                    let (x, y) = (reader.usize()?, reader.usize()?);
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.place(x, y, material, tint, spread);
+                }
+                OP_PAINT => {
+                    let (x1, y1, x2, y2) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let radius = reader.usize()?;
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.paint(x1, y1, x2, y2, radius, material, tint, spread);
+                }
+                OP_SIMULATE => {
+                    world.simulate();
+
+                    check(&mut reader, &world)?;
+                }
+                OP_PLACE_WITH_TEMP => {
+                    let (x, y) = (reader.usize()?, reader.usize()?);
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+                    let temperature = reader.i16()?;
+
+                    world.place_with_temp(x, y, material, tint, spread, temperature);
+                }
+                OP_RESET => {
+                    world.reset();
+                }
+                OP_RESET_REGION => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.reset_region(x, y, width, height);
+                }
+                OP_SET_EROSION => {
+                    world.set_erosion(reader.bool()?);
+                }
+                OP_PLACE_DEFAULT => {
+                    let (x, y, material) = (reader.usize()?, reader.usize()?, reader.material()?);
+
+                    world.place_default(x, y, material);
+                }
+                OP_PLACE_NATURAL => {
+                    let (x, y, material) = (reader.usize()?, reader.usize()?, reader.material()?);
+
+                    world.place_natural(x, y, material);
+                }
+                OP_PAINT_TAPERED => {
+                    let (x1, y1, r1) = (reader.usize()?, reader.usize()?, reader.usize()?);
+                    let (x2, y2, r2) = (reader.usize()?, reader.usize()?, reader.usize()?);
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.paint_tapered(x1, y1, r1, x2, y2, r2, material, tint, spread);
+                }
+                OP_PAINT_DEFAULT => {
+                    let (x1, y1, x2, y2) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let (radius, material) = (reader.usize()?, reader.material()?);
+
+                    world.paint_default(x1, y1, x2, y2, radius, material);
+                }
+                OP_PAINT_MIXED => {
+                    let (x1, y1, x2, y2) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let radius = reader.usize()?;
+                    let (materials, weights) = (reader.list()?, reader.list()?);
+                    let (tint, spread) = (reader.tint()?, reader.u8()?);
+
+                    world.paint_mixed(x1, y1, x2, y2, radius, materials, weights, tint, spread)?;
+                }
+                OP_FILL_RECT => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.fill_rect(x, y, width, height, material, tint, spread);
+                }
+                OP_FILL_RECT_PATTERN => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let pattern = PatternFill::read(&mut reader)?;
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.fill_rect_pattern(x, y, width, height, &pattern, material, tint, spread);
+                }
+                OP_PLACE_POLYGON => {
+                    let points = read_points(&mut reader)?;
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.place_polygon(&points, material, tint, spread);
+                }
+                OP_PLACE_POLYGON_PATTERN => {
+                    let points = read_points(&mut reader)?;
+                    let pattern = PatternFill::read(&mut reader)?;
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.place_polygon_pattern(&points, &pattern, material, tint, spread);
+                }
+                OP_STAMP_MASK => {
+                    let (x, y, mask_width, mask_height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let (alpha, threshold) = (reader.list()?, reader.u8()?);
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.stamp_mask(
+                        x,
+                        y,
+                        mask_width,
+                        mask_height,
+                        alpha,
+                        threshold,
+                        material,
+                        tint,
+                        spread,
+                    );
+                }
+                OP_SCATTER => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+                    let coverage = reader.u8()?;
+
+                    world.scatter(x, y, width, height, material, tint, spread, coverage);
+                }
+                OP_FILL_AIR => {
+                    let (material, tint, spread) =
+                        (reader.material()?, reader.tint()?, reader.u8()?);
+
+                    world.fill_air(material, tint, spread);
+                }
+                OP_INVERT_REGION => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.invert_region(x, y, width, height, reader.material()?);
+                }
+                OP_PASTE_REGION => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.paste_region(x, y, width, height, reader.list()?)?;
+                }
+                OP_SET_TINT_RECT => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.set_tint_rect(x, y, width, height, reader.tint()?);
+                }
+                OP_SET_SPREAD_RECT => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.set_spread_rect(x, y, width, height, reader.u8()?);
+                }
+                OP_SET_SPREAD_GLOBAL => {
+                    let (material, spread) = (reader.material()?, reader.u8()?);
+
+                    world.set_spread_global(material, spread);
+                }
+                OP_SET_CELL_COLOR_OVERRIDE => {
+                    let (x, y, rgba) = (reader.usize()?, reader.usize()?, reader.u32()?);
+
+                    world.set_cell_color_override(x, y, rgba);
+                }
+                OP_CLEAR_CELL_COLOR_OVERRIDE => {
+                    let (x, y) = (reader.usize()?, reader.usize()?);
+
+                    world.clear_cell_color_override(x, y);
+                }
+                OP_RESTORE => {
+                    let snapshot = Snapshot::read(&mut reader, world.materials.len())?;
+
+                    world.restore(snapshot);
+                }
+                OP_REWIND => {
+                    let (ticks, rng) = (reader.u64()?, reader.u64()?);
+                    let snapshot = Snapshot::read(&mut reader, world.materials.len())?;
+
+                    world.finish_tick();
+                    world.restore(snapshot);
+                    world.ticks = ticks;
+                    world.rng.state = rng;
+                }
+                OP_RESIZE => {
+                    let (width, height) = (reader.usize()?, reader.usize()?);
+
+                    check_replay_size(width, height)?;
+
+                    world.resize(width, height, Anchor::try_from(reader.u8()?)?);
+                }
+                OP_REBUILD_CHUNKS => {
+                    world.rebuild_chunks(reader.usize()?);
+                }
+                OP_SHRINK_TO_WORLD => {
+                    world.shrink_to_world();
+                }
+                OP_WARM_ALL => {
+                    world.warm_all();
+                }
+                OP_SIMULATE_REGION => {
+                    let (x, y, width, height) = (
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                        reader.usize()?,
+                    );
+
+                    world.simulate_region(x, y, width, height);
+
+                    check(&mut reader, &world)?;
+                }
+                OP_SIMULATE_BUDGETED => {
+                    let mut rows = reader.u32()?;
+                    let complete = reader.bool()?;
+
+                    world.simulate_rows(|| {
+                        rows = rows.saturating_sub(1);
+                        rows > 0 || complete
+                    });
+
+                    check(&mut reader, &world)?;
+                }
+                OP_DEBUG_STEP => {
+                    world.debug_step();
+
+                    check(&mut reader, &world)?;
+                }
+                OP_SET_DIRTY_RECTS => {
+                    world.set_dirty_rects(reader.bool()?);
+                }
+                OP_SET_MAX_FALL_SPEED => {
+                    world.set_max_fall_speed(reader.u8()?);
+                }
+                OP_SET_MAX_SPREAD_PER_TICK => {
+                    world.set_max_spread_per_tick(reader.u8()?);
+                }
+                OP_SET_FLOW_BIAS => {
+                    world.set_flow_bias(reader.u8()? as i8);
+                }
+                OP_SET_SIMULATION_BOUNDS => {
+                    let (x, y) = (reader.u64()? as usize, reader.u64()? as usize);
+                    let (width, height) = (reader.u64()? as usize, reader.u64()? as usize);
+
+                    world.set_simulation_bounds(x, y, width, height);
+                }
+                OP_CLEAR_SIMULATION_BOUNDS => {
+                    world.clear_simulation_bounds();
+                }
+                OP_SET_SUBSTEPS => {
+                    world.set_substeps(reader.u8()?);
+                }
+                OP_SET_TICK_RATE_DIVISOR => {
+                    world.set_tick_rate_divisor(reader.u8()?);
+                }
+                OP_SET_MATERIAL_PAUSED => {
+                    let (material, paused) = (reader.material()?, reader.bool()?);
+
+                    world.set_material_paused(material, paused);
+                }
+                OP_SET_DEFAULT_SPREAD => {
+                    let (material, spread) = (reader.material()?, reader.u8()?);
+
+                    world.set_default_spread(material, spread);
+                }
+                OP_SET_DEFAULT_TINT_MODE => {
+                    let material = reader.material()?;
+
+                    world.set_default_tint_mode(material, TintMode::try_from(reader.u8()?)?);
+                }
+                OP_SET_UPDATE_MODE => {
+                    world.set_update_mode(UpdateMode::try_from(reader.u8()?)?);
+                }
+                OP_SET_SEED => {
+                    world.set_seed(reader.u64()?);
+                }
+                OP_SET_AMBIENT_TEMPERATURE => {
+                    world.set_ambient_temperature(reader.i16()?);
+                }
+                OP_SET_EVAPORATION => {
+                    world.set_evaporation(reader.bool()?);
+                }
+                OP_SET_SURFACE_TENSION => {
+                    world.set_surface_tension(reader.bool()?);
+                }
+                OP_SET_PARTICLE_LIMIT => {
+                    world.set_particle_limit(reader.u32()?);
+                }
+                OP_SET_MAX_ACTIVE_CHUNKS => {
+                    world.set_max_active_chunks(reader.u32()?);
+                }
+                OP_LOAD_PRESET => {
+                    world.load_preset(Preset::try_from(reader.u8()?)?);
+                }
+                OP_GENERATE_TERRAIN => {
+                    let seed = reader.u64()?;
+                    let (ground_level, roughness) = (reader.usize()?, reader.usize()?);
+                    let options = TerrainOptions::new(ground_level, roughness, reader.usize()?);
+
+                    world.generate_terrain(seed, &options);
+                }
+                OP_GENERATE_CAVES => {
+                    let seed = reader.u64()?;
+                    let (fill_probability, smoothing_passes) = (reader.u8()?, reader.u8()?);
+
+                    world.generate_caves(seed, fill_probability, smoothing_passes);
+                }
+                OP_SET_STRIPE_ROWS => {
+                    world.set_stripe_rows(reader.u32()?);
+                }
+                op => return Err(format!("{} is not a recorded operation", op)),
+            }
+        }
+
+        Ok(world)
+    }
+
+    /// Lets falling sand accelerate by one cell per tick, up to `speed`. Defaults to 1, which
+    /// disables acceleration.
+    pub fn set_max_fall_speed(&mut self, speed: u8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_MAX_FALL_SPEED, speed]);
+        }
+
+        self.max_fall_speed = speed.max(1);
+    }
+
+    /// Limits how many cells anything may spread sideways in a single tick. Defaults to 255.
+    pub fn set_max_spread_per_tick(&mut self, cap: u8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_MAX_SPREAD_PER_TICK, cap]);
+        }
+
+        self.spread_cap = cap;
+    }
+
+    /// Makes liquids favor flowing right for a positive `bias`, or left for a negative one, with a
+    /// `|bias|` in 128 chance per move. Defaults to 0.
+    pub fn set_flow_bias(&mut self, bias: i8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_FLOW_BIAS, bias as u8]);
+        }
+
+        self.flow_bias = bias;
+    }
+
+    /// Confines every tick to the rectangle at `(x, y)`, whose edges behave like the edge of the
+    /// world, until `clear_simulation_bounds` is called.
+    pub fn set_simulation_bounds(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_SIMULATION_BOUNDS);
+
+            for value in [x, y, width, height].iter() {
+                write_u64(log, *value as u64);
+            }
+        }
+
+        self.simulation_bounds = if width == 0 || height == 0 {
+            Bounds::EMPTY
+        } else {
+            Bounds {
+                left: x,
+                top: y,
+                right: x.saturating_add(width - 1),
+                bottom: y.saturating_add(height - 1),
+            }
+        };
+
+        // Material along the old edges may be free to move now.
+        self.warm_all_chunks();
+    }
+
+    /// Lets ticks cover the whole world again, waking up whatever fell asleep outside the bounds.
+    pub fn clear_simulation_bounds(&mut self) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_CLEAR_SIMULATION_BOUNDS);
+        }
+
+        self.simulation_bounds = Bounds::ALL;
+        self.warm_all_chunks();
+    }
+
+    /// Splits every call to `simulate` into `substeps` passes, with falling material covering an
+    /// equal share of its distance in each. Defaults to 1.
+    pub fn set_substeps(&mut self, substeps: u8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_SUBSTEPS, substeps]);
+        }
+
+        self.substeps = substeps.max(1);
+    }
+
+    /// Only moves material on ticks that are a multiple of `divisor`, while reactions carry on
+    /// every tick. Defaults to 1.
+    pub fn set_tick_rate_divisor(&mut self, divisor: u8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_TICK_RATE_DIVISOR, divisor]);
+        }
+
+        self.tick_rate_divisor = divisor.max(1);
+    }
+
+    /// Stops a material from moving or reacting on its own. Other materials can still swap with it.
+    pub fn set_material_paused(&mut self, material: Material, paused: bool) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_MATERIAL_PAUSED, material as u8, paused as u8]);
+        }
+
+        let was_paused = std::mem::replace(&mut self.paused[material as usize], paused);
+
+        if !was_paused || paused {
+            return;
+        }
+
+        // The material may have gone to sleep while it was paused, so wake it back up.
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if self.materials[y * self.size.width + x] == material {
+                    self.warm_up(x, y);
+                }
+            }
+        }
+
+        self.flush_forecast();
+    }
+
+    /// The same as `set_material_paused(material, !enabled)`.
+    #[deprecated(note = "use `set_material_paused` instead")]
+    // The generated bindings call it like anyone else.
+    #[cfg_attr(feature = "wasm", allow(deprecated))]
+    pub fn set_material_enabled(&mut self, material: Material, enabled: bool) {
+        self.set_material_paused(material, !enabled);
+    }
+
+    pub fn set_default_spread(&mut self, material: Material, spread: u8) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_DEFAULT_SPREAD, material as u8, spread]);
+        }
+
+        self.default_spreads[material as usize] = spread;
+    }
+
+    pub fn set_default_tint_mode(&mut self, material: Material, mode: TintMode) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_DEFAULT_TINT_MODE, material as u8, mode as u8]);
+        }
+
+        self.default_tints[material as usize] = mode;
+    }
+
+    /// Changes the dimensions of the world, keeping existing content positioned according to
+    /// `anchor`. Content that no longer fits is clipped.
+    pub fn resize(&mut self, width: usize, height: usize, anchor: Anchor) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_RESIZE);
+            write_u32(log, width as u32);
+            write_u32(log, height as u32);
+            log.push(anchor as u8);
+        }
+
+        let old = self.size;
+        let size = Size { width, height };
+
+        let (dx, dy) = match anchor {
+            Anchor::TopLeft => (0, 0),
+            Anchor::BottomLeft => (0, height as isize - old.height as isize),
+            Anchor::Center => (
+                (width as isize - old.width as isize) / 2,
+                (height as isize - old.height as isize) / 2,
+            ),
+        };
+
+        self.materials = remap(&self.materials, old, size, dx, dy, Material::Air);
+        self.tints = remap(&self.tints, old, size, dx, dy, Tint::None);
+        self.spreads = remap(&self.spreads, old, size, dx, dy, 0);
+        self.lives = remap(&self.lives, old, size, dx, dy, 0);
+        self.velocities = remap(&self.velocities, old, size, dx, dy, 0);
+        self.temperatures = remap(
+            &self.temperatures,
+            old,
+            size,
+            dx,
+            dy,
+            self.ambient_temperature,
+        );
+        if !self.watched.is_empty() {
+            self.watched = remap(&self.watched, old, size, dx, dy, Material::Air);
+        }
+
+        self.color_overrides = self
+            .color_overrides
+            .drain()
+            .filter_map(|(index, rgba)| {
+                let x = (index % old.width) as isize + dx;
+                let y = (index / old.width) as isize + dy;
+
+                if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+                    return None;
+                }
+
+                Some((y as usize * width + x as usize, rgba))
+            })
+            .collect();
+        self.dirty = DirtyCells::new(width * height);
+        self.front = Vec::new();
+
+        // Abandon any unfinished tick; its rows no longer line up with the world.
+        self.tick = None;
+        self.window = Bounds::ALL;
+
+        self.size = size;
+        self.recount();
+
+        // Chunks have to line up with the new size before any cell can be reported.
+        self.resize_chunks(self.chunk_size);
+
+        // Every cell may have moved, so report all of them.
+        self.changes.clear();
+        self.changed = vec![false; width * height];
+        self.motion = vec![false; width * height];
+        self.moving.clear();
+        self.active_cells = 0;
+
+        for i in 0..self.materials.len() {
+            self.note_change(i);
+        }
+
+        // Snapshots from before the resize no longer line up with the world.
+        if let Some(history) = self.history.as_mut() {
+            history.undo.clear();
+            history.redo.clear();
+        }
+
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.frames.clear();
+            rewind.elapsed = 0;
+        }
+    }
+
+    /// Frees whatever memory the world's buffers hold beyond what its current size needs. Pointers
+    /// handed out earlier may be invalidated.
+    pub fn shrink_to_world(&mut self) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SHRINK_TO_WORLD);
+        }
+
+        // The scratch buffers of an unfinished tick are still in use.
+        self.finish_tick();
+
+        self.materials.shrink_to_fit();
+        self.row_counts.shrink_to_fit();
+        self.dirty.generations.shrink_to_fit();
+        self.changes.shrink_to_fit();
+        self.changed.shrink_to_fit();
+        self.cells.shrink_to_fit();
+        self.cells_pending.shrink_to_fit();
+        self.rgba = Vec::new();
+        self.motion.shrink_to_fit();
+        self.moving.shrink_to_fit();
+        self.tints.shrink_to_fit();
+        self.spreads.shrink_to_fit();
+        self.lives.shrink_to_fit();
+        self.velocities.shrink_to_fit();
+        self.temperatures.shrink_to_fit();
+        self.color_overrides.shrink_to_fit();
+        self.front = Vec::new();
+        self.watched.shrink_to_fit();
+
+        self.neighborhoods.shrink_to_fit();
+        self.active_chunks.words.shrink_to_fit();
+        self.forecast.words.shrink_to_fit();
+        self.fenced.words.shrink_to_fit();
+        self.activity.shrink_to_fit();
+        self.touched_chunks.shrink_to_fit();
+        self.listed_chunks.shrink_to_fit();
+        self.chunk_list.shrink_to_fit();
+        self.changed_chunks.words.shrink_to_fit();
+        self.deferrals.shrink_to_fit();
+        self.chunk_stats.shrink_to_fit();
+        self.active_bounds.shrink_to_fit();
+        self.forecast_bounds.shrink_to_fit();
+    }
+
+    /// Changes the size of the chunks the world is divided into, waking every chunk with anything
+    /// in it.
+    pub fn rebuild_chunks(&mut self, chunk_size: usize) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_REBUILD_CHUNKS);
+            write_u32(log, chunk_size as u32);
+        }
+
+        // An unfinished tick is tracked in chunks of the old size.
+        self.finish_tick();
+
+        self.resize_chunks(chunk_size.max(1));
+
+        self.hot = false;
+
+        self.active_chunks.clear();
+
+        for entry in self.active_bounds.iter_mut() {
+            *entry = Bounds::EMPTY;
+        }
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if self.materials[y * self.size.width + x] == Material::Air {
+                    continue;
+                }
+
+                let (cx, cy) = self.world_to_chunk(x, y);
+                let index = cy * self.chunk_columns + cx;
+
+                self.active_chunks.set(index, true);
+                self.active_bounds[index] = Bounds::ALL;
+                self.hot = true;
+            }
+        }
+    }
+
+    /// Chooses how each tick applies its moves. Switching modes takes effect on the next tick.
+    pub fn set_update_mode(&mut self, mode: UpdateMode) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_UPDATE_MODE, mode as u8]);
+        }
+
+        self.update_mode = mode;
+
+        // The front buffer is still needed if a tick is in progress, and is freed once it ends.
+        if mode == UpdateMode::InPlace && self.tick.is_none() {
+            self.front = Vec::new();
+        }
+    }
+
+    /// Shorthand for switching between `UpdateMode::DoubleBuffered` and `UpdateMode::InPlace`.
+    pub fn set_double_buffered(&mut self, enabled: bool) {
+        self.set_update_mode(if enabled {
+            UpdateMode::DoubleBuffered
+        } else {
+            UpdateMode::InPlace
+        });
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_SEED);
+            write_u64(log, seed);
+        }
+
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn set_ambient_temperature(&mut self, temperature: i16) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_AMBIENT_TEMPERATURE);
+            log.extend_from_slice(&temperature.to_le_bytes());
+        }
+
+        self.ambient_temperature = temperature;
+
+        self.warm_all_chunks();
+    }
+
+    pub fn set_evaporation(&mut self, enabled: bool) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_EVAPORATION, enabled as u8]);
+        }
+
+        self.evaporation = enabled;
+
+        self.warm_all_chunks();
+    }
+
+    /// When enabled, rock slowly crumbles into sand or air wherever moving water brushes past it.
+    pub fn set_erosion(&mut self, enabled: bool) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_EROSION, enabled as u8]);
+        }
+
+        if self.erosion != enabled {
+            self.erosion = enabled;
+            self.warm_all_chunks();
+        }
+    }
+
+    /// Makes water hold together in droplets rather than thinning out into films one cell thick.
+    /// Lone droplets still fall and flow freely.
+    pub fn set_surface_tension(&mut self, enabled: bool) {
+        if let Some(log) = self.recording.as_mut() {
+            log.extend_from_slice(&[OP_SET_SURFACE_TENSION, enabled as u8]);
+        }
+
+        self.surface_tension = enabled;
+
+        self.warm_all_chunks();
+    }
+
+    /// Calls `callback` once each time the world settles. It runs in the middle of a tick, so it
+    /// should not touch the world.
+    #[cfg(feature = "wasm")]
+    pub fn set_idle_callback(&mut self, callback: &js_sys::Function) {
+        self.idle_callback = Some(Callback::Js(callback.clone()));
+    }
+
+    pub fn clear_idle_callback(&mut self) {
+        self.idle_callback = None;
+    }
+
+    /// Calls `callback` once per tick with `[x, y, created, ...]` triples for every cell where
+    /// `material` appeared (1) or disappeared (0).
+    #[cfg(feature = "wasm")]
+    pub fn set_cell_callback(&mut self, material: Material, callback: &js_sys::Function) {
+        self.watch(material, Callback::Js(callback.clone()));
+    }
+
+    #[cfg(any(feature = "wasm", test))]
+    fn watch(&mut self, material: Material, callback: Callback) {
+        if self.watched.is_empty() {
+            self.watched = self.materials.clone();
+        }
+
+        self.cell_callbacks[material as usize] = Some(callback);
+    }
+
+    pub fn clear_cell_callback(&mut self, material: Material) {
+        self.cell_callbacks[material as usize] = None;
+
+        if self
+            .cell_callbacks
+            .iter()
+            .all(|callback| callback.is_none())
+        {
+            self.watched = Vec::new();
+        }
+    }
+
+    /// Wakes up every chunk, so the next tick scans the whole world.
+    pub fn warm_all(&mut self) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_WARM_ALL);
+        }
+
+        self.warm_all_chunks();
+    }
+
+    /// Returns true while anything in the world may still move.
+    pub fn is_active(&self) -> bool {
+        self.hot || self.active_chunks.any() || self.forecast.any()
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Returns how many cells the world holds, the length of the cell buffers.
+    pub fn cell_count(&self) -> usize {
+        self.size.width * self.size.height
+    }
+
+    /// Returns the position of the cell at (x, y) in the cell buffers, or nothing if it is outside
+    /// of the world.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.size.width || y >= self.size.height {
+            return None;
+        }
+
+        Some(y * self.size.width + x)
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn chunk_columns(&self) -> usize {
+        self.chunk_columns
+    }
+
+    pub fn chunk_rows(&self) -> usize {
+        self.chunk_rows
+    }
+
+    /// Returns the index of every cell whose material or tint changed during the last tick, or was
+    /// edited since. Each cell is listed once.
+    pub fn changes_ptr(&self) -> *const u32 {
+        self.changes.as_ptr()
+    }
+
+    pub fn changes_len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns a byte per chunk: 0 if idle, 1 if active, 2 if forecast, or 3 if waiting on the
+    /// world outside the last `simulate_region`.
+    pub fn chunk_activity(&self) -> *const u8 {
+        self.activity.as_ptr()
+    }
+
+    pub fn chunk_activity_len(&self) -> usize {
+        self.activity.len()
+    }
+
+    /// Returns the index of every chunk that was scanned during the last tick or holds a cell
+    /// listed by `changes_ptr`.
+    pub fn active_chunk_list_ptr(&self) -> *const u32 {
+        self.chunk_list.as_ptr()
+    }
+
+    pub fn active_chunk_list_len(&self) -> usize {
+        self.chunk_list.len()
+    }
+
+    /// Returns the chunks that changed since the last call, as flat `[x, y, width, height, ...]`
+    /// rectangles in cells, merged where they line up.
+    pub fn changed_rects(&mut self) -> Vec<u32> {
+        // (left, top, right, bottom), in chunks, with the right and bottom edges exclusive.
+        let mut rects: Vec<(usize, usize, usize, usize)> = Vec::new();
+        // Rects that reach down to the row of chunks before the current one.
+        let mut open: Vec<(usize, usize, usize, usize)> = Vec::new();
+
+        for cy in 0..self.chunk_rows {
+            let mut next = Vec::new();
+            let mut cx = 0;
+
+            while cx < self.chunk_columns {
+                if !self.changed_chunks.get(cy * self.chunk_columns + cx) {
+                    cx += 1;
+                    continue;
+                }
+
+                let left = cx;
+
+                while cx < self.chunk_columns
+                    && self.changed_chunks.get(cy * self.chunk_columns + cx)
+                {
+                    cx += 1;
+                }
+
+                match open.iter().position(|&(l, _, r, _)| l == left && r == cx) {
+                    Some(i) => {
+                        let (left, top, right, _) = open.swap_remove(i);
+
+                        next.push((left, top, right, cy + 1));
+                    }
+                    None => next.push((left, cy, cx, cy + 1)),
+                }
+            }
+
+            // Whatever did not carry on into this row is finished.
+            rects.append(&mut open);
+            open = next;
+        }
+
+        rects.append(&mut open);
+        self.changed_chunks.clear();
+
+        rects
+            .into_iter()
+            .flat_map(|(left, top, right, bottom)| {
+                let x = left * self.chunk_size;
+                let y = top * self.chunk_size;
+                let width = (right * self.chunk_size).min(self.size.width) - x;
+                let height = (bottom * self.chunk_size).min(self.size.height) - y;
+
+                [x as u32, y as u32, width as u32, height as u32]
+            })
+            .collect()
+    }
+
+    /// Returns how many ticks have completed since the world was created or last reset.
+    pub fn tick(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns whether each cell moved during the last tick, including whatever was left behind.
+    pub fn moved(&self) -> *const bool {
+        self.motion.as_ptr()
+    }
+
+    /// Returns how many cells moved during the last tick, counting a cell that moved more than
+    /// once, or traded places with another, only once.
+    pub fn count_active_cells(&self) -> usize {
+        self.active_cells as usize
+    }
+
+    /// The same as `particle_count`.
+    pub fn non_air_count(&self) -> usize {
+        self.particle_count() as usize
+    }
+
+    /// Returns how many cells hold something other than air.
+    pub fn particle_count(&self) -> u32 {
+        self.materials.len() as u32 - self.counts[Material::Air as usize]
+    }
+
+    /// Verifies that the world's bookkeeping is consistent, returning a message naming the first
+    /// problem found.
+    #[cfg(feature = "debug-checks")]
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut counts = [0; MATERIAL_COUNT];
+
+        for &material in self.materials.iter() {
+            counts[material as usize] += 1;
+        }
+
+        for (material, (&expected, &actual)) in counts.iter().zip(self.counts.iter()).enumerate() {
+            if expected != actual {
+                return Err(format!(
+                    "the count of material {} is {}, but the world holds {}",
+                    material, actual, expected
+                ));
+            }
+        }
+
+        for (y, row) in self.materials.chunks(self.size.width.max(1)).enumerate() {
+            let expected = row
+                .iter()
+                .filter(|&&material| material != Material::Air)
+                .count();
+
+            if self.row_counts.get(y) != Some(&(expected as u32)) {
+                return Err(format!(
+                    "row {} is counted as holding {:?} cells, but it holds {}",
+                    y,
+                    self.row_counts.get(y),
+                    expected
+                ));
+            }
+        }
+
+        if self.tick.is_some() {
+            return Ok(());
+        }
+
+        if let Some(index) = self.dirty.first() {
+            return Err(format!(
+                "cell ({}, {}) is dirty between ticks",
+                index % self.size.width,
+                index / self.size.width
+            ));
+        }
+
+        for i in 0..self.active_chunks.len() {
+            let (cx, cy) = (i % self.chunk_columns, i / self.chunk_columns);
+
+            if self.moved_chunks[i] && !self.active_chunks.get(i) && !self.forecast.get(i) {
+                return Err(format!(
+                    "chunk ({}, {}) had material move into it, but is asleep",
+                    cx, cy
+                ));
+            }
+
+            let (left, top, right, bottom) = self.last_region;
+            let scanned = cx >= left && cx < right && cy >= top && cy < bottom;
+
+            if self.forecast.get(i) && scanned {
+                return Err(format!("chunk ({}, {}) is forecast between ticks", cx, cy));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns how many cells hold each material, indexed by the material's value.
+    pub fn counts_ptr(&self) -> *const u32 {
+        self.counts.as_ptr()
+    }
+
+    /// Caps how many cells may hold something other than air. Placing into empty cells past the cap
+    /// does nothing. Defaults to `u32::MAX`.
+    pub fn set_particle_limit(&mut self, max: u32) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_PARTICLE_LIMIT);
+            write_u32(log, max);
+        }
+
+        self.particle_limit = max;
+    }
+
+    /// Caps how many chunks a single tick scans, holding the rest back for later ticks,
+    /// longest-waiting first. Defaults to `u32::MAX`.
+    pub fn set_max_active_chunks(&mut self, max: u32) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_MAX_ACTIVE_CHUNKS);
+            write_u32(log, max);
+        }
+
+        self.max_active_chunks = max.max(1);
+    }
+
+    /// Splits every tick of the whole world into horizontal stripes of `rows` chunk rows, which
+    /// take turns in two phases: first every other stripe, starting with the top one, then the
+    /// rest. Each stripe runs on a world of its own that also holds the chunk row to either side,
+    /// so material can cross into its neighbors, and no two stripes in a phase share a cell. With
+    /// the `parallel` feature, the stripes of a phase run on separate threads; either way, a seed
+    /// plays out the same. Stripes cover at least 2 chunk rows. Defaults to 0, which turns
+    /// striping off.
+    pub fn set_stripe_rows(&mut self, rows: u32) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_STRIPE_ROWS);
+            write_u32(log, rows);
+        }
+
+        self.stripe_rows = match rows {
+            0 => 0,
+            rows => rows.max(2) as usize,
+        };
+    }
+
+    /// Starts or stops counting the moves made in each chunk every tick.
+    pub fn enable_chunk_stats(&mut self, enabled: bool) {
+        self.chunk_stats = if enabled {
+            vec![0; self.chunk_columns * self.chunk_rows]
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Returns how many moves started in each chunk during the last tick.
+    pub fn chunk_stats(&self) -> *const u16 {
+        self.chunk_stats.as_ptr()
+    }
+
+    pub fn chunk_stats_len(&self) -> usize {
+        self.chunk_stats.len()
+    }
+
+    /// Returns how many chunks will be scanned by the next call to `simulate`.
+    pub fn active_chunk_count(&self) -> usize {
+        self.active_chunks.count()
+    }
+
+    pub fn materials(&self) -> *const Material {
+        self.materials.as_ptr()
+    }
+
+    pub fn tints(&self) -> *const Tint {
+        self.tints.as_ptr()
+    }
+
+    pub fn materials_len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn tints_len(&self) -> usize {
+        self.tints.len()
+    }
+
+    /// Describes how the buffers returned by `materials` and `tints` are laid out.
+    pub fn layout(&self) -> BufferLayout {
+        BufferLayout {
+            element_size: std::mem::size_of::<Material>(),
+            row_stride: self.size.width * std::mem::size_of::<Material>(),
+            order: BufferOrder::RowMajor,
+        }
+    }
+
+    /// Returns a view of the material of every cell, straight out of wasm memory. The view is only
+    /// valid until the next call into the world: any `place`, `simulate` or `resize` may reallocate
+    /// the buffer or grow wasm memory, which leaves the view reading garbage or detached. Take a
+    /// new view every frame rather than holding on to one.
+    #[cfg(feature = "wasm")]
+    pub fn materials_view(&self) -> js_sys::Uint8Array {
+        // SAFETY: `Material` is `repr(u8)`, so the buffer is `len` initialized bytes, borrowed for
+        // as long as `self` is.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(self.materials.as_ptr() as *const u8, self.materials.len())
+        };
+
+        // SAFETY: the view aliases the buffer without owning it. Nothing in Rust can touch the
+        // buffer until control returns to JavaScript, and from then on the caller is responsible
+        // for dropping the view before the world is called into again, as documented above.
+        unsafe { js_sys::Uint8Array::view(bytes) }
+    }
+
+    /// Returns a view of the tint of every cell, valid only until the next call into the world,
+    /// like `materials_view`.
+    #[cfg(feature = "wasm")]
+    pub fn tints_view(&self) -> js_sys::Uint8Array {
+        // SAFETY: `Tint` is `repr(u8)`, so the buffer is `len` initialized bytes, borrowed for as
+        // long as `self` is.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(self.tints.as_ptr() as *const u8, self.tints.len())
+        };
+
+        // SAFETY: as in `materials_view`, the caller must drop the view before calling into the
+        // world again.
+        unsafe { js_sys::Uint8Array::view(bytes) }
+    }
+
+    /// Returns every cell packed as `material | tint << 8`. Only cells changed since the last call
+    /// are repacked.
+    pub fn cells_ptr(&mut self) -> *const u16 {
+        self.pack_cells();
+
+        self.cells.as_ptr()
+    }
+
+    /// Brings `cells` up to date with every cell changed since it was last packed.
+    fn pack_cells(&mut self) {
+        let pack = |material: Material, tint: Tint| material as u16 | (tint as u16) << 8;
+
+        if self.cells_rebuild || self.cells.len() != self.materials.len() {
+            self.cells = self
+                .materials
+                .iter()
+                .zip(self.tints.iter())
+                .map(|(&material, &tint)| pack(material, tint))
+                .collect();
+        } else {
+            for &index in self.cells_pending.iter() {
+                let index = index as usize;
+
+                self.cells[index] = pack(self.materials[index], self.tints[index]);
+            }
+        }
+
+        self.cells_pending.clear();
+        self.cells_rebuild = false;
+    }
+
+    /// Draws every cell as four `RGBA` bytes: its material's default color darkened by its tint,
+    /// or its color override if it has one.
+    pub fn render_rgba(&mut self) -> *const u8 {
+        self.render();
+
+        self.rgba.as_ptr()
+    }
+
+    fn render(&mut self) {
+        self.rgba.resize(self.materials.len() * 4, 0);
+
+        for (index, pixel) in self.rgba.chunks_exact_mut(4).enumerate() {
+            let rgba = self.tints[index].darken(material_color(self.materials[index]));
+
+            pixel.copy_from_slice(&rgba.to_be_bytes());
+        }
+
+        for (&index, &rgba) in self.color_overrides.iter() {
+            self.rgba[index * 4..index * 4 + 4].copy_from_slice(&rgba.to_be_bytes());
+        }
+    }
+
+    /// Returns the temperature of every cell, row by row, in degrees Celsius.
+    pub fn temperatures(&self) -> *const i16 {
+        self.temperatures.as_ptr()
+    }
+
+    /// Draws the cell at (x, y) in a custom `0xRRGGBBAA` color. The color moves with the cell, and
+    /// is dropped once something else is placed there.
+    pub fn set_cell_color_override(&mut self, x: usize, y: usize, rgba: u32) {
+        if x >= self.size.width || y >= self.size.height {
+            return;
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_SET_CELL_COLOR_OVERRIDE);
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+            write_u32(log, rgba);
+        }
+
+        let index = y * self.size.width + x;
+
+        self.color_overrides.insert(index, rgba);
+        self.note_change(index);
+    }
+
+    pub fn clear_cell_color_override(&mut self, x: usize, y: usize) {
+        if x >= self.size.width || y >= self.size.height {
+            return;
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_CLEAR_CELL_COLOR_OVERRIDE);
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+        }
+
+        let index = y * self.size.width + x;
+
+        if self.color_overrides.remove(&index).is_some() {
+            self.note_change(index);
+        }
+    }
+
+    pub fn cell_color_override(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.size.width || y >= self.size.height {
+            return None;
+        }
+
+        self.color_overrides
+            .get(&(y * self.size.width + x))
+            .copied()
+    }
+
+    /// Returns every color override as `[index, rgba, ...]` pairs, sorted by index.
+    pub fn color_overrides(&self) -> Vec<u32> {
+        let mut entries: Vec<(usize, u32)> = self
+            .color_overrides
+            .iter()
+            .map(|(&index, &rgba)| (index, rgba))
+            .collect();
+
+        entries.sort_unstable();
+
+        entries
+            .into_iter()
+            .flat_map(|(index, rgba)| [index as u32, rgba])
+            .collect()
+    }
+
+    /// Returns true if any cell within the rectangle, clipped to the world, holds `material`.
+    pub fn contains_material_in_rect(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+    ) -> bool {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right {
+            return false;
+        }
+
+        (y..bottom).any(|j| {
+            let row = j * self.size.width;
+
+            self.materials[row + x..row + right].contains(&material)
+        })
+    }
+
+    /// Returns the material of every cell in the world along the Bresenham line from `(x1, y1)` to
+    /// `(x2, y2)`.
+    pub fn material_at_cursor_line(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> Vec<u8> {
+        let mut result = Vec::new();
+
+        bresenham(
+            x1 as isize,
+            y1 as isize,
+            x2 as isize,
+            y2 as isize,
+            |x, y| {
+                let (x, y) = (x as usize, y as usize);
+
+                if x < self.size.width && y < self.size.height {
+                    result.push(self.materials[y * self.size.width + x] as u8);
+                }
+            },
+        );
+
+        result
+    }
+
+    /// Returns the material at each linear index in `indices`, or 255 for an index outside the
+    /// world.
+    pub fn get_many(&self, indices: &[u32]) -> Vec<u8> {
+        indices
+            .iter()
+            .map(|&index| {
+                self.materials
+                    .get(index as usize)
+                    .map_or(OUT_OF_RANGE, |&material| material as u8)
+            })
+            .collect()
+    }
+
+    /// Returns an FNV-1a hash of the world's size and the material, tint and spread of every cell.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+
+        hash = fnv1a(hash, (self.size.width as u32).to_le_bytes().iter().copied());
+        hash = fnv1a(
+            hash,
+            (self.size.height as u32).to_le_bytes().iter().copied(),
+        );
+        hash = fnv1a(hash, self.materials.iter().map(|&material| material as u8));
+        hash = fnv1a(hash, self.tints.iter().map(|&tint| tint as u8));
+        hash = fnv1a(hash, self.spreads.iter().copied());
+
+        hash
+    }
+
+    /// Writes the material of every cell as comma-separated numbers, one row per line. Refuses
+    /// worlds larger than 256x256.
+    pub fn export_csv(&self) -> Result<String, String> {
+        if self.materials.len() > CSV_CELL_LIMIT {
+            return Err(format!(
+                "a {}x{} world is too large to export",
+                self.size.width, self.size.height
+            ));
+        }
+
+        let mut csv = String::new();
+
+        for row in self.materials.chunks(self.size.width.max(1)) {
+            let line: Vec<String> = row
+                .iter()
+                .map(|&material| (material as u8).to_string())
+                .collect();
+
+            csv.push_str(&line.join(","));
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+
+    /// Copies a rectangle of the world: a material byte per cell, followed by tint and spread bytes
+    /// when `attributes` is set.
+    pub fn get_region(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        attributes: bool,
+    ) -> Result<Vec<u8>, String> {
+        let stride = if attributes { 3 } else { 1 };
+        let len = width
+            .checked_mul(height)
+            .and_then(|cells| cells.checked_mul(stride))
+            .ok_or_else(|| format!("a {}x{} region is too large", width, height))?;
+        let mut data = Vec::with_capacity(len);
+
+        for j in 0..height {
+            let row = y.checked_add(j).filter(|&row| row < self.size.height);
+
+            for i in 0..width {
+                let column = x.checked_add(i).filter(|&column| column < self.size.width);
+
+                let (material, tint, spread) = if let (Some(i), Some(j)) = (column, row) {
+                    let index = j * self.size.width + i;
+
+                    (
+                        self.materials[index],
+                        self.tints[index],
+                        self.spreads[index],
+                    )
+                } else {
+                    (Material::Air, Tint::None, 0)
+                };
+
+                data.push(material as u8);
+
+                if attributes {
+                    data.push(tint as u8);
+                    data.push(spread);
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Writes a rectangle produced by `get_region` back into the world at `(x, y)`, clipped to the
+    /// world's bounds.
+    pub fn paste_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<u32, String> {
+        let area = width.checked_mul(height);
+
+        let stride = if area == Some(data.len()) {
+            1
+        } else if area.and_then(|area| area.checked_mul(3)) == Some(data.len()) {
+            3
+        } else {
+            return Err(format!(
+                "expected 1 or 3 bytes per cell for a {}x{} region, but got {}",
+                width,
+                height,
+                data.len()
+            ));
+        };
+
+        let mut cells = Vec::with_capacity(width * height);
+
+        for cell in data.chunks_exact(stride) {
+            let material = Material::try_from(cell[0])?;
+            let (tint, spread) = if stride == 3 {
+                (Tint::try_from(cell[1])?, cell[2])
+            } else {
+                (Tint::None, 0)
+            };
+
+            cells.push((material, tint, spread));
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_PASTE_REGION);
+
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            write_list(log, data);
+        }
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for j in 0..height.min(self.size.height.saturating_sub(y)) {
+            for i in 0..width.min(self.size.width.saturating_sub(x)) {
+                let (material, tint, spread) = cells[j * width + i];
+
+                changed += self.put(x + i, y + j, material, tint, spread) as u32;
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+
+        Ok(changed)
+    }
+
+    /// Returns the temperature that newly placed `material` starts out at.
+    fn default_temperature(&self, material: Material) -> i16 {
+        match material {
+            Material::Fire => FIRE_TEMPERATURE,
+            Material::Steam => STEAM_TEMPERATURE,
+            Material::Ice => ICE_TEMPERATURE,
+            _ => self.ambient_temperature,
+        }
+    }
+
+    fn default_tint(&mut self, material: Material) -> Tint {
+        match self.default_tints[material as usize] {
+            TintMode::None => Tint::None,
+            TintMode::Faint => Tint::Faint,
+            TintMode::Dark => Tint::Dark,
+            TintMode::Shaded => Tint::Shaded,
+            TintMode::Darker => Tint::Darker,
+            TintMode::Deep => Tint::Deep,
+            TintMode::Darkest => Tint::Darkest,
+            TintMode::Deepest => Tint::Deepest,
+            TintMode::Random => self.random_tint(),
+        }
+    }
+
+    fn random_tint(&mut self) -> Tint {
+        Tint::LEVELS[(self.rng.next() % TINT_LEVELS as u64) as usize]
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<&Material> {
+        // Anything beyond the region being simulated is treated like the edge of the world.
+        if !self.window.contains(x, y) {
+            return None;
+        }
+
+        // The front buffer is only populated while a double-buffered tick is in progress.
+        if self.front.is_empty() {
+            self.materials.get(y * self.size.width + x)
+        } else {
+            self.front.get(y * self.size.width + x)
+        }
+    }
+
+    fn get_chunk_index(&self, x: usize, y: usize) -> Option<usize> {
+        let (x, y) = self.world_to_chunk(x, y);
+
+        let index = y * self.chunk_columns + x;
+
+        if index < self.active_chunks.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_RESET);
+        }
+
+        self.clear();
+    }
+
+    /// Empties the world without recording it, for edits that are made up of more than a reset.
+    fn clear(&mut self) {
+        self.ticks = 0;
+
+        // Abandon any unfinished tick; there is nothing left for it to finish.
+        if self.tick.take().is_some() {
+            self.dirty.clear();
+            self.front.clear();
+        }
+
+        self.window = Bounds::ALL;
+
+        for i in 0..self.materials.len() {
+            if self.materials[i] != Material::Air || self.tints[i] != Tint::None {
+                self.note_change(i);
+            }
+
+            self.set_material(i, Material::Air);
+            self.tints[i] = Tint::None;
+            self.spreads[i] = 0;
+            self.lives[i] = 0;
+            self.velocities[i] = 0;
+            self.temperatures[i] = self.ambient_temperature;
+        }
+
+        let overridden: Vec<usize> = self
+            .color_overrides
+            .drain()
+            .map(|(index, _)| index)
+            .collect();
+
+        for index in overridden {
+            self.note_change(index);
+        }
+
+        self.hot = false;
+
+        self.active_chunks.clear();
+        self.forecast.clear();
+        self.fenced.clear();
+
+        for i in 0..self.activity.len() {
+            self.activity[i] = 0;
+            self.deferrals[i] = 0;
+            self.active_bounds[i] = Bounds::EMPTY;
+            self.forecast_bounds[i] = Bounds::EMPTY;
+        }
+    }
+
+    /// Replaces the world with layered rock and sand terrain, with water pooled below the water
+    /// table.
+    pub fn generate_terrain(&mut self, seed: u64, options: &TerrainOptions) {
+        let log = self.record(OP_GENERATE_TERRAIN, |log| {
+            write_u64(log, seed);
+
+            for value in [options.ground_level, options.roughness, options.water_table].iter() {
+                write_u32(log, *value as u32);
+            }
+        });
+
+        self.begin_edit();
+        self.clear();
+
+        let width = self.size.width;
+        let height = self.size.height;
+
+        // Hills span roughly a quarter of the world.
+        let scale = 4.0 / width.max(1) as f32;
+        let water_line = height.saturating_sub(options.water_table);
+
+        for x in 0..width {
+            let noise = fbm(seed, x as f32 * scale, 4);
+            let elevation =
+                options.ground_level as f32 + (noise * 2.0 - 1.0) * options.roughness as f32;
+            let surface = height.saturating_sub(elevation.max(0.0) as usize);
+
+            let noise = fbm(seed.wrapping_add(0x5a4d), x as f32 * scale * 4.0, 2);
+            let depth = 2 + (noise * 6.0) as usize;
+
+            for y in water_line.min(surface)..height {
+                let (material, spread) = if y >= surface + depth {
+                    (Material::Rock, 0)
+                } else if y >= surface {
+                    (Material::Sand, 1)
+                } else {
+                    (Material::Water, 5)
+                };
+
+                self.write(y * width + x, material, Tint::None, spread);
+
+                // Buried ground cannot move, so only the surface and the water above it need to.
+                if y <= surface {
+                    self.warm_up(x, y);
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+    }
+
+    /// Replaces the world with rock riddled with caves, flooding whatever is open in the bottom
+    /// quarter.
+    pub fn generate_caves(&mut self, seed: u64, fill_probability: u8, smoothing_passes: u8) {
+        let log = self.record(OP_GENERATE_CAVES, |log| {
+            write_u64(log, seed);
+            log.extend_from_slice(&[fill_probability, smoothing_passes]);
+        });
+
+        self.begin_edit();
+        self.clear();
+
+        let width = self.size.width;
+        let height = self.size.height;
+
+        let mut rng = Rng::new(seed);
+
+        let mut solid: Vec<bool> = (0..width * height)
+            .map(|_| rng.next() % 255 < fill_probability as u64)
+            .collect();
+        let mut next = solid.clone();
+
+        for _ in 0..smoothing_passes {
+            for y in 0..height {
+                for x in 0..width {
+                    let mut walls = 0;
+
+                    for j in y as isize - 1..=y as isize + 1 {
+                        for i in x as isize - 1..=x as isize + 1 {
+                            if i == x as isize && j == y as isize {
+                                continue;
+                            }
+
+                            // The edge of the world counts as solid rock.
+                            if i < 0
+                                || j < 0
+                                || i >= width as isize
+                                || j >= height as isize
+                                || solid[j as usize * width + i as usize]
+                            {
+                                walls += 1;
+                            }
+                        }
+                    }
+
+                    let index = y * width + x;
+
+                    next[index] = if solid[index] { walls >= 4 } else { walls >= 5 };
+                }
+            }
+
+            std::mem::swap(&mut solid, &mut next);
+        }
+
+        let water_line = height - height / 4;
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+
+                if solid[index] {
+                    self.write(index, Material::Rock, Tint::None, 0);
+                } else if y >= water_line && self.write(index, Material::Water, Tint::None, 5) {
+                    // Rock never moves on its own, so only the pools need to settle.
+                    self.warm_up(x, y);
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+    }
+
+    /// Resets the world and builds one of the preset scenes, scaled to the current world size.
+    pub fn load_preset(&mut self, preset: Preset) {
+        let log = self.record(OP_LOAD_PRESET, |log| log.push(preset as u8));
+
+        // Loading a preset can be undone in one step, like any other edit.
+        self.begin_edit();
+
+        self.clear();
+
+        let width = self.size.width;
+        let height = self.size.height;
+
+        // Scales a fraction of the world's width or height to a cell coordinate.
+        let x = |fraction: f32| (width as f32 * fraction) as u32;
+        let y = |fraction: f32| (height as f32 * fraction) as u32;
+
+        match preset {
+            Preset::Hourglass => {
+                let glass = [
+                    x(0.2),
+                    y(0.05),
+                    x(0.8),
+                    y(0.05),
+                    x(0.52),
+                    y(0.5),
+                    x(0.8),
+                    y(0.95),
+                    x(0.2),
+                    y(0.95),
+                    x(0.48),
+                    y(0.5),
+                ];
+                let bulb = [
+                    x(0.2),
+                    y(0.05),
+                    x(0.8),
+                    y(0.05),
+                    x(0.55),
+                    y(0.45),
+                    x(0.45),
+                    y(0.45),
+                ];
+
+                self.fill_rect(0, 0, width, height, Material::Rock, Tint::Dark, 0);
+                self.place_polygon(&glass, Material::Air, Tint::None, 0);
+                self.place_polygon(&bulb, Material::Sand, Tint::None, 1);
+            }
+            Preset::Waterfall => {
+                let cliff = [0, y(0.35), x(0.4), y(0.35), x(0.45), y(1.0), 0, y(1.0)];
+
+                self.place_polygon(&cliff, Material::Rock, Tint::None, 0);
+                self.fill_rect(
+                    0,
+                    y(0.05) as usize,
+                    x(0.35) as usize,
+                    y(0.3) as usize,
+                    Material::Water,
+                    Tint::None,
+                    5,
+                );
+                self.fill_rect(
+                    x(0.45) as usize,
+                    y(0.9) as usize,
+                    width,
+                    height,
+                    Material::Sand,
+                    Tint::None,
+                    1,
+                );
+            }
+            Preset::Volcano => {
+                let cone = [
+                    x(0.1),
+                    y(1.0),
+                    x(0.42),
+                    y(0.4),
+                    x(0.58),
+                    y(0.4),
+                    x(0.9),
+                    y(1.0),
+                ];
+                let crater = [x(0.45), y(0.4), x(0.55), y(0.4), x(0.5), y(0.5)];
+
+                self.place_polygon(&cone, Material::Rock, Tint::Darker, 0);
+                self.place_polygon(&crater, Material::Fire, Tint::None, 0);
+                self.fill_rect(
+                    x(0.45) as usize,
+                    y(0.2) as usize,
+                    x(0.1) as usize,
+                    y(0.15) as usize,
+                    Material::Smoke,
+                    Tint::None,
+                    3,
+                );
+                self.paint(
+                    x(0.3) as usize,
+                    y(0.1) as usize,
+                    x(0.7) as usize,
+                    y(0.1) as usize,
+                    (height / 40).max(1),
+                    Material::Sand,
+                    Tint::None,
+                    1,
+                );
+            }
+            Preset::Aquarium => {
+                let wall = (width.min(height) / 40).max(1);
+
+                self.fill_rect(0, 0, width, height, Material::Rock, Tint::Darkest, 0);
+                self.fill_rect(
+                    wall,
+                    0,
+                    width.saturating_sub(2 * wall),
+                    height.saturating_sub(wall),
+                    Material::Water,
+                    Tint::None,
+                    5,
+                );
+                self.fill_rect(
+                    wall,
+                    0,
+                    width.saturating_sub(2 * wall),
+                    y(0.15) as usize,
+                    Material::Air,
+                    Tint::None,
+                    0,
+                );
+                self.fill_rect(
+                    wall,
+                    y(0.85) as usize,
+                    width.saturating_sub(2 * wall),
+                    height,
+                    Material::Sand,
+                    Tint::None,
+                    1,
+                );
+
+                let rock = [x(0.6), y(0.86), x(0.68), y(0.7), x(0.75), y(0.86)];
+
+                self.place_polygon(&rock, Material::Rock, Tint::Dark, 0);
+            }
+        }
+
+        self.end_edit();
+        self.resume_recording(log);
+    }
+
+    pub fn place(&mut self, x: usize, y: usize, material: Material, tint: Tint, spread: u8) -> u32 {
+        let log = self.record(OP_PLACE, |log| {
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        self.begin_edit();
+
+        let changed = self.put(x, y, material, tint, spread);
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed as u32
+    }
+
+    /// Same as `place`, but the material starts out at `temperature` instead of its default.
+    pub fn place_with_temp(
+        &mut self,
+        x: usize,
+        y: usize,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+        temperature: i16,
+    ) -> u32 {
+        let log = self.record(OP_PLACE_WITH_TEMP, |log| {
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+            log.extend_from_slice(&temperature.to_le_bytes());
+        });
+
+        self.begin_edit();
+
+        let changed = self.put(x, y, material, tint, spread);
+
+        if self.get(x, y) == Some(&material) {
+            self.temperatures[y * self.size.width + x] = temperature;
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed as u32
+    }
+
+    pub fn set_tint(&mut self, x: usize, y: usize, tint: Tint) {
+        self.set_tint_rect(x, y, 1, 1, tint);
+    }
+
+    pub fn set_tint_rect(&mut self, x: usize, y: usize, width: usize, height: usize, tint: Tint) {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right || y >= bottom {
+            return;
+        }
+
+        let log = self.record(OP_SET_TINT_RECT, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.push(tint as u8);
+        });
+
+        self.begin_edit();
+
+        for j in y..bottom {
+            for i in x..right {
+                let index = j * self.size.width + i;
+
+                if self.tints[index] == tint {
+                    continue;
+                }
+
+                if let Some(history) = self.history.as_mut() {
+                    history.touched = true;
+                }
+
+                self.tints[index] = tint;
+                self.note_change(index);
+            }
+        }
+
+        self.end_edit();
+        self.resume_recording(log);
+    }
+
+    pub fn set_spread(&mut self, x: usize, y: usize, spread: u8) {
+        self.set_spread_rect(x, y, 1, 1, spread);
+    }
+
+    pub fn set_spread_rect(&mut self, x: usize, y: usize, width: usize, height: usize, spread: u8) {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right || y >= bottom {
+            return;
+        }
+
+        let log = self.record(OP_SET_SPREAD_RECT, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.push(spread);
+        });
+
+        self.max_spread = self.max_spread.max(spread);
+
+        for j in y..bottom {
+            for i in x..right {
+                self.spreads[j * self.size.width + i] = spread;
+
+                // The material did not move, but it may be able to now.
+                self.warm_up(i, j);
+            }
+        }
+
+        self.flush_forecast();
+        self.resume_recording(log);
+    }
+
+    /// Changes the spread of every cell that currently holds `material`.
+    pub fn set_spread_global(&mut self, material: Material, spread: u8) {
+        let log = self.record(OP_SET_SPREAD_GLOBAL, |log| {
+            log.extend_from_slice(&[material as u8, spread]);
+        });
+
+        self.max_spread = self.max_spread.max(spread);
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let index = y * self.size.width + x;
+
+                if self.materials[index] != material {
+                    continue;
+                }
+
+                self.spreads[index] = spread;
+
+                if self.tick.is_some() {
+                    self.dirty.mark(index);
+                }
+
+                self.warm_up(x, y);
+            }
+        }
+
+        self.flush_forecast();
+        self.resume_recording(log);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_FILL_RECT, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        let changed = self.fill_rect_with(x, y, width, height, None, material, tint, spread);
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Clears a rectangle to air, waking the cells around it. Returns the number of cells cleared.
+    pub fn reset_region(&mut self, x: usize, y: usize, width: usize, height: usize) -> u32 {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right || y >= bottom {
+            return 0;
+        }
+
+        let log = self.record(OP_RESET_REGION, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+        });
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for j in y..bottom {
+            for i in x..right {
+                changed += self.put(i, j, Material::Air, Tint::None, 0) as u32;
+            }
+        }
+
+        // Wake the ring of cells bordering the rectangle, even if it was already empty.
+        let left = x.saturating_sub(1);
+        let top = y.saturating_sub(1);
+        let right = (right + 1).min(self.size.width);
+        let bottom = (bottom + 1).min(self.size.height);
+
+        for j in top..bottom {
+            for i in left..right {
+                if j == top || j == bottom - 1 || i == left || i == right - 1 {
+                    self.warm_up(i, j);
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Turns air into `material` and `material` into air within a rectangle. Returns the number of
+    /// cells changed.
+    pub fn invert_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+    ) -> u32 {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if material == Material::Air || x >= right || y >= bottom {
+            return 0;
+        }
+
+        let log = self.record(OP_INVERT_REGION, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.push(material as u8);
+        });
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for j in y..bottom {
+            for i in x..right {
+                let current = self.materials[j * self.size.width + i];
+
+                if current == Material::Air {
+                    let tint = self.default_tint(material);
+                    let spread = self.default_spreads[material as usize];
+
+                    changed += self.put(i, j, material, tint, spread) as u32;
+                } else if current == material {
+                    changed += self.put(i, j, Material::Air, Tint::None, 0) as u32;
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_rect_pattern(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pattern: &PatternFill,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_FILL_RECT_PATTERN, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            pattern.write(log);
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        let changed =
+            self.fill_rect_with(x, y, width, height, Some(pattern), material, tint, spread);
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Places material wherever the alpha of a `mask_width` by `mask_height` mask, positioned at
+    /// `(x, y)`, exceeds `threshold`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stamp_mask(
+        &mut self,
+        x: usize,
+        y: usize,
+        mask_width: usize,
+        mask_height: usize,
+        alpha: &[u8],
+        threshold: u8,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_STAMP_MASK, |log| {
+            for value in [x, y, mask_width, mask_height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            write_list(log, alpha);
+            log.extend_from_slice(&[threshold, material as u8, tint as u8, spread]);
+        });
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for j in 0..mask_height.min(self.size.height.saturating_sub(y)) {
+            for i in 0..mask_width.min(self.size.width.saturating_sub(x)) {
+                let value = j
+                    .checked_mul(mask_width)
+                    .and_then(|row| row.checked_add(i))
+                    .and_then(|k| alpha.get(k));
+
+                match value {
+                    Some(&value) if value > threshold => {
+                        changed += self.put(x + i, y + j, material, tint, spread) as u32;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Places material into roughly `coverage / 255` of the air cells of a rectangle. Returns the
+    /// number of cells placed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scatter(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+        coverage: u8,
+    ) -> u32 {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right || y >= bottom {
+            return 0;
+        }
+
+        let log = self.record(OP_SCATTER, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.extend_from_slice(&[material as u8, tint as u8, spread, coverage]);
+        });
+
+        let area = (right - x) * (bottom - y);
+        let target = (area * coverage as usize + 127) / 255;
+
+        let mut empty = Vec::new();
+
+        for j in y..bottom {
+            for i in x..right {
+                if self.materials[j * self.size.width + i] == Material::Air {
+                    empty.push((i, j));
+                }
+            }
+        }
+
+        let target = target.min(empty.len());
+
+        self.begin_edit();
+
+        let mut placed = 0;
+
+        // A partial Fisher-Yates shuffle picks `target` cells without replacement.
+        for i in 0..target {
+            let j = i + (self.rng.next() % (empty.len() - i) as u64) as usize;
+
+            empty.swap(i, j);
+
+            let (i, j) = empty[i];
+
+            placed += self.put(i, j, material, tint, spread) as u32;
+        }
+
+        if target > 0 {
+            self.flush_forecast();
+        }
+
+        self.end_edit();
+        self.resume_recording(log);
+
+        placed
+    }
+
+    /// Fills every cell that is currently air with `material`, leaving everything else untouched.
+    pub fn fill_air(&mut self, material: Material, tint: Tint, spread: u8) -> u32 {
+        let log = self.record(OP_FILL_AIR, |log| {
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if self.materials[y * self.size.width + x] == Material::Air {
+                    changed += self.put(x, y, material, tint, spread) as u32;
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Places a material using its default tint mode and spread.
+    pub fn place_default(&mut self, x: usize, y: usize, material: Material) -> u32 {
+        let log = self.record(OP_PLACE_DEFAULT, |log| {
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+            log.push(material as u8);
+        });
+
+        let tint = self.default_tint(material);
+        let changed = self.place(
+            x,
+            y,
+            material,
+            tint,
+            self.default_spreads[material as usize],
+        );
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Places a material using its default spread and a random tint, seeded by the world.
+    pub fn place_natural(&mut self, x: usize, y: usize, material: Material) -> u32 {
+        let log = self.record(OP_PLACE_NATURAL, |log| {
+            write_u32(log, x as u32);
+            write_u32(log, y as u32);
+            log.push(material as u8);
+        });
+
+        let tint = self.random_tint();
+        let changed = self.place(
+            x,
+            y,
+            material,
+            tint,
+            self.default_spreads[material as usize],
+        );
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    pub fn place_polygon(
+        &mut self,
+        points: &[u32],
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_PLACE_POLYGON, |log| {
+            write_points(log, points);
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        let changed = self.place_polygon_with(points, None, material, tint, spread);
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    pub fn place_polygon_pattern(
+        &mut self,
+        points: &[u32],
+        pattern: &PatternFill,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_PLACE_POLYGON_PATTERN, |log| {
+            write_points(log, points);
+            pattern.write(log);
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        let changed = self.place_polygon_with(points, Some(pattern), material, tint, spread);
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn fill_rect_with(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pattern: Option<&PatternFill>,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        for j in y..bottom {
+            for i in x..right {
+                changed += self.fill(i, j, pattern, material, tint, spread) as u32;
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+
+        changed
+    }
+
+    fn place_polygon_with(
+        &mut self,
+        points: &[u32],
+        pattern: Option<&PatternFill>,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let vertices: Vec<(f32, f32)> = points
+            .chunks_exact(2)
+            .map(|point| (point[0] as f32, point[1] as f32))
+            .collect();
+
+        if vertices.len() < 3 {
+            return 0;
+        }
+
+        let top = vertices.iter().map(|v| v.1).fold(f32::MAX, f32::min);
+        let bottom = vertices.iter().map(|v| v.1).fold(f32::MIN, f32::max);
+
+        let top = top.max(0.0) as usize;
+        let bottom = (bottom.ceil() as usize).min(self.size.height);
+
+        let mut crossings: Vec<f32> = Vec::new();
+        let mut changed = 0;
+
+        self.begin_edit();
+
+        for y in top..bottom {
+            // Sample each row through the center of its cells.
+            let scanline = y as f32 + 0.5;
+
+            crossings.clear();
+
+            for i in 0..vertices.len() {
+                let (x1, y1) = vertices[i];
+                let (x2, y2) = vertices[(i + 1) % vertices.len()];
+
+                if (y1 <= scanline) != (y2 <= scanline) {
+                    crossings.push(x1 + (scanline - y1) / (y2 - y1) * (x2 - x1));
+                }
+            }
+
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            // Even-odd rule: the interior lies between every other pair of crossings.
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil().max(0.0) as usize;
+                let end = ((span[1] - 0.5).ceil().max(0.0) as usize).min(self.size.width);
+
+                for x in start..end {
+                    changed += self.fill(x, y, pattern, material, tint, spread) as u32;
+                }
+            }
+        }
+
+        self.flush_forecast();
+        self.end_edit();
+
+        changed
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            materials: self.materials.clone(),
+            tints: self.tints.clone(),
+            spreads: self.spreads.clone(),
+            lives: self.lives.clone(),
+            velocities: self.velocities.clone(),
+            temperatures: self.temperatures.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        for i in 0..self.materials.len() {
+            if self.materials[i] != snapshot.materials[i] || self.tints[i] != snapshot.tints[i] {
+                self.note_change(i);
+            }
+        }
+
+        self.materials = snapshot.materials;
+        self.recount();
+        self.tints = snapshot.tints;
+        self.spreads = snapshot.spreads;
+        self.lives = snapshot.lives;
+        self.velocities = snapshot.velocities;
+        self.temperatures = snapshot.temperatures;
+
+        self.warm_all_chunks();
+    }
+
+    /// Marks the start of an edit, capturing the world as it was if history is enabled.
+    fn begin_edit(&mut self) {
+        let snapshot = match &self.history {
+            Some(history) if history.depth == 0 => Some(self.snapshot()),
+            _ => None,
+        };
+
+        if let Some(history) = self.history.as_mut() {
+            if snapshot.is_some() {
+                history.pending = snapshot;
+                history.touched = false;
+            }
+
+            history.depth += 1;
+        }
+    }
+
+    /// Marks the end of an edit, saving history once the outermost edit finishes.
+    fn end_edit(&mut self) {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return,
+        };
+
+        history.depth = history.depth.saturating_sub(1);
+
+        if history.depth > 0 {
+            return;
+        }
+
+        if let Some(snapshot) = history.pending.take() {
+            if history.touched {
+                if history.undo.len() == history.capacity {
+                    history.undo.pop_front();
+                }
+
+                history.undo.push_back(snapshot);
+                history.redo.clear();
+            }
+        }
+    }
+
+    /// Records that the material or tint of a cell changed.
+    fn note_change(&mut self, index: usize) {
+        if self.changes_stale {
+            self.clear_changes();
+        }
+
+        if !self.cells_rebuild {
+            // Past a point, repacking everything is cheaper than keeping track.
+            if self.cells_pending.len() >= self.materials.len() {
+                self.cells_pending.clear();
+                self.cells_rebuild = true;
+            } else {
+                self.cells_pending.push(index as u32);
+            }
+        }
+
+        let (cx, cy) = self.world_to_chunk(index % self.size.width, index / self.size.width);
+        let chunk = cy * self.chunk_columns + cx;
+
+        // Unlike the list of changes, this carries over from tick to tick until it is read.
+        self.changed_chunks.set(chunk, true);
+
+        if !self.changed[index] {
+            self.changed[index] = true;
+            self.changes.push(index as u32);
+
+            self.touched_chunks[chunk] = true;
+        }
+    }
+
+    /// Moves a touched chunk onto the chunk list, unless it is already there.
+    fn list_chunk(&mut self, index: usize) {
+        if self.touched_chunks[index] && !self.listed_chunks[index] {
+            self.listed_chunks[index] = true;
+            self.chunk_list.push(index as u32);
+        }
+
+        self.touched_chunks[index] = false;
+    }
+
+    /// Lists every touched chunk, for ticks that never reach the sweep in `end_tick`.
+    fn list_touched_chunks(&mut self) {
+        for i in 0..self.touched_chunks.len() {
+            self.list_chunk(i);
+        }
+    }
+
+    /// Hands whatever appeared or disappeared since the last flush to the matching cell callbacks.
+    fn flush_cell_events(&mut self) {
+        if self.watched.is_empty() {
+            return;
+        }
+
+        let mut events: [Vec<u32>; MATERIAL_COUNT] = Default::default();
+
+        for &index in self.changes.iter() {
+            let index = index as usize;
+            let before = self.watched[index];
+            let after = self.materials[index];
+
+            if before == after {
+                continue;
+            }
+
+            self.watched[index] = after;
+
+            let x = (index % self.size.width) as u32;
+            let y = (index / self.size.width) as u32;
+
+            if self.cell_callbacks[before as usize].is_some() {
+                events[before as usize].extend_from_slice(&[x, y, 0]);
+            }
+
+            if self.cell_callbacks[after as usize].is_some() {
+                events[after as usize].extend_from_slice(&[x, y, 1]);
+            }
+        }
+
+        for (callback, events) in self.cell_callbacks.iter().zip(events.iter()) {
+            if let (Some(callback), false) = (callback, events.is_empty()) {
+                callback.call_with(events);
+            }
+        }
+    }
+
+    /// Replaces the material in a cell, keeping the material counts up to date.
+    fn set_material(&mut self, index: usize, material: Material) {
+        let row = index / self.size.width;
+
+        match (
+            self.materials[index] == Material::Air,
+            material == Material::Air,
+        ) {
+            (true, false) => self.row_counts[row] += 1,
+            (false, true) => self.row_counts[row] -= 1,
+            _ => (),
+        }
+
+        self.counts[self.materials[index] as usize] -= 1;
+        self.counts[material as usize] += 1;
+        self.materials[index] = material;
+    }
+
+    /// Counts every material from scratch, after the whole world was replaced at once.
+    fn recount(&mut self) {
+        self.counts = [0; MATERIAL_COUNT];
+        self.row_counts = vec![0; self.size.height];
+
+        for (index, &material) in self.materials.iter().enumerate() {
+            self.counts[material as usize] += 1;
+
+            if material != Material::Air {
+                self.row_counts[index / self.size.width] += 1;
+            }
+        }
+    }
+
+    /// Counts how many cells in a row are air, starting at `x` and heading towards `preference`, up
+    /// to `limit`.
+    fn air_run(&self, x: usize, y: usize, preference: isize, limit: usize) -> usize {
+        let row = &self.materials[y * self.size.width..(y + 1) * self.size.width];
+        let word = |cells: &[Material]| {
+            let mut bytes = [0; 8];
+
+            for (byte, &material) in bytes.iter_mut().zip(cells.iter()) {
+                *byte = material as u8;
+            }
+
+            u64::from_le_bytes(bytes)
+        };
+
+        let mut run = 0;
+
+        while run < limit {
+            let (start, end) = if preference < 0 {
+                (x + 1 - run - 8.min(limit - run), x + 1 - run)
+            } else {
+                (x + run, x + run + 8.min(limit - run))
+            };
+
+            if end - start == 8 && word(&row[start..end]) == 0 {
+                run += 8;
+                continue;
+            }
+
+            let cell = if preference < 0 { x - run } else { x + run };
+
+            if row[cell] != Material::Air {
+                break;
+            }
+
+            run += 1;
+        }
+
+        run
+    }
+
+    /// Forgets which cells moved, ahead of a new tick.
+    fn clear_motion(&mut self) {
+        for &index in self.moving.iter() {
+            self.motion[index as usize] = false;
+        }
+
+        self.moving.clear();
+    }
+
+    fn clear_changes(&mut self) {
+        for &index in self.changes.iter() {
+            self.changed[index as usize] = false;
+        }
+
+        self.changes.clear();
+        self.changes_stale = false;
+
+        for &index in self.chunk_list.iter() {
+            self.listed_chunks[index as usize] = false;
+        }
+
+        self.chunk_list.clear();
+    }
+
+    /// Writes a cell, returning whether its material changed.
+    fn put(&mut self, x: usize, y: usize, material: Material, tint: Tint, spread: u8) -> bool {
+        if x >= self.size.width || y >= self.size.height {
+            return false;
+        }
+
+        let index = y * self.size.width + x;
+        let previous = self.materials[index];
+
+        if !self.write(index, material, tint, spread) {
+            return false;
+        }
+
+        // Keep the cell still for the rest of a tick that is in progress.
+        if self.tick.is_some() {
+            self.dirty.mark(index);
+        }
+
+        self.warm_up(x, y);
+
+        previous != material
+    }
+
+    /// Writes every channel of a cell without waking anything up, leaving that to the caller.
+    /// Returns false if the particle limit left no room for the material.
+    fn write(&mut self, index: usize, material: Material, tint: Tint, spread: u8) -> bool {
+        if material != Material::Air
+            && self.materials[index] == Material::Air
+            && self.particle_count() >= self.particle_limit
+        {
+            return false;
+        }
+
+        if let Some(history) = self.history.as_mut() {
+            history.touched = true;
+        }
+
+        self.set_material(index, material);
+        self.tints[index] = tint;
+        self.spreads[index] = spread;
+        self.color_overrides.remove(&index);
+        self.note_change(index);
+        self.max_spread = self.max_spread.max(spread);
+        self.lives[index] = material.life();
+        self.velocities[index] = 0;
+        self.temperatures[index] = self.default_temperature(material);
+
+        true
+    }
+
+    fn fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        pattern: Option<&PatternFill>,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> bool {
+        if let Some(pattern) = pattern {
+            if !pattern.contains(x, y) {
+                return false;
+            }
+        }
+
+        self.put(x, y, material, tint, spread)
+    }
+
+    /// Reallocates the chunk grid for the current world size, waking every chunk.
+    fn resize_chunks(&mut self, chunk_size: usize) {
+        let columns = (self.size.width as f32 / chunk_size as f32).ceil() as usize;
+        let rows = (self.size.height as f32 / chunk_size as f32).ceil() as usize;
+
+        self.chunk_size = chunk_size;
+        self.chunk_columns = columns;
+        self.chunk_rows = rows;
+        self.neighborhoods = Neighborhood::all(columns, rows);
+
+        self.active_chunks = BitSet::new(columns * rows);
+        self.forecast = BitSet::new(columns * rows);
+        self.fenced = BitSet::new(columns * rows);
+        self.activity = vec![0; columns * rows];
+        self.touched_chunks = vec![false; columns * rows];
+        self.listed_chunks = vec![false; columns * rows];
+        // Changes that have yet to be reported no longer line up with the chunks.
+        self.changed_chunks = BitSet::new(columns * rows);
+        self.changed_chunks.fill();
+        self.deferrals = vec![0; columns * rows];
+
+        #[cfg(feature = "debug-checks")]
+        {
+            self.moved_chunks = vec![false; columns * rows];
+        }
+
+        self.chunk_list.clear();
+
+        if !self.chunk_stats.is_empty() {
+            self.chunk_stats = vec![0; columns * rows];
+        }
+        self.active_bounds = vec![Bounds::EMPTY; columns * rows];
+        self.forecast_bounds = vec![Bounds::EMPTY; columns * rows];
+
+        self.warm_all_chunks();
+    }
+
+    fn warm_all_chunks(&mut self) {
+        self.active_chunks.fill();
+        self.fenced.clear();
+
+        for entry in self.active_bounds.iter_mut() {
+            *entry = Bounds::ALL;
+        }
+
+        self.hot = true;
+    }
+
+    /// Gives exposed surface water a chance to turn into steam. Returns true if it did.
+    fn evaporate(&mut self, x: usize, y: usize) -> bool {
+        if !self.evaporation || self.ambient_temperature <= EVAPORATION_POINT || y == 0 {
+            return false;
+        }
+
+        if self.get(x, y - 1) != Some(&Material::Air) {
+            return false;
+        }
+
+        // Keep the surface simulating so the puddle continues to dry out.
+        self.warm_up(x, y);
+
+        if !self.rng.chance(EVAPORATION_ODDS) {
+            return false;
+        }
+
+        let index = y * self.size.width + x;
+
+        self.set_material(index, Material::Steam);
+        self.dirty.mark(index);
+        self.note_change(index);
+
+        true
+    }
+
+    /// Lets acid eat away at a neighboring solid, or dilutes it in water. Returns true if the acid
+    /// was diluted.
+    fn corrode(&mut self, x: usize, y: usize) -> bool {
+        let mut water = 0;
+        let mut solid = false;
+
+        for offset in NEIGHBORS.iter() {
+            match self
+                .neighbor(x, y, *offset)
+                .and_then(|(i, j)| self.get(i, j))
+            {
+                Some(Material::Water) => water += 1,
+                Some(material) if State::from(*material) == State::Solid => solid = true,
+                _ => (),
+            }
+        }
+
+        if water < DILUTION_NEIGHBORS && !solid {
+            return false;
+        }
+
+        // Keep simulating until the reaction has run its course.
+        self.warm_up(x, y);
+
+        let index = y * self.size.width + x;
+
+        if water >= DILUTION_NEIGHBORS {
+            if !self.rng.chance(DILUTION_ODDS) {
+                return false;
+            }
+
+            // The acid turns into water itself, so no water is made out of nothing.
+            self.set_material(index, Material::Water);
+            self.dirty.mark(index);
+            self.note_change(index);
+
+            return true;
+        }
+
+        if !self.rng.chance(CORROSION_ODDS) {
+            return false;
+        }
+
+        let offset = NEIGHBORS[(self.rng.next() % NEIGHBORS.len() as u64) as usize];
+
+        let (i, j) = match self.neighbor(x, y, offset) {
+            Some(neighbor) => neighbor,
+            None => return false,
+        };
+
+        let eaten = j * self.size.width + i;
+
+        if State::from(self.materials[eaten]) != State::Solid {
+            return false;
+        }
+
+        if self.rng.chance(CORROSION_SMOKE_ODDS) {
+            self.set_material(eaten, Material::Smoke);
+            self.spreads[eaten] = self.default_spreads[Material::Smoke as usize];
+        } else {
+            self.set_material(eaten, Material::Air);
+            self.spreads[eaten] = 0;
+        }
+
+        self.tints[eaten] = Tint::None;
+        self.lives[eaten] = 0;
+        self.velocities[eaten] = 0;
+        self.dirty.mark(eaten);
+        self.note_change(eaten);
+
+        self.warm_up(i, j);
+
+        false
+    }
+
+    /// Gives cold water on the floor, or next to ice, a chance to freeze. Returns true if it did.
+    fn freeze(&mut self, x: usize, y: usize) -> bool {
+        if self.ambient_temperature >= FREEZING_POINT {
+            return false;
+        }
+
+        // The bottom of the world is as cold as rock.
+        let floor = matches!(self.get(x, y + 1), None | Some(Material::Rock));
+
+        let touching_ice = NEIGHBORS
+            .iter()
+            .any(|offset| match self.neighbor(x, y, *offset) {
+                // Ice that froze this tick is not cold enough yet, so a pool freezes one layer at a
+                // time.
+                Some((i, j)) => {
+                    self.get(i, j) == Some(&Material::Ice)
+                        && !self.dirty.get(j * self.size.width + i)
+                }
+                None => false,
+            });
+
+        if !floor && !touching_ice {
+            return false;
+        }
+
+        // Keep the pool simulating so it continues to freeze.
+        self.warm_up(x, y);
+
+        if !self.rng.chance(FREEZE_ODDS) {
+            return false;
+        }
+
+        let index = y * self.size.width + x;
+
+        self.set_material(index, Material::Ice);
+        self.velocities[index] = 0;
+        self.dirty.mark(index);
+        self.note_change(index);
+
+        true
+    }
+
+    /// Gives ice a chance to melt back into water once the world is warm enough.
+    fn melt(&mut self, x: usize, y: usize) {
+        if self.ambient_temperature <= MELTING_POINT {
+            return;
+        }
+
+        self.warm_up(x, y);
+
+        if !self.rng.chance(MELT_ODDS) {
+            return;
+        }
+
+        let index = y * self.size.width + x;
+
+        self.set_material(index, Material::Water);
+        self.dirty.mark(index);
+        self.note_change(index);
+    }
+
+    /// Gives rock touching water that just flowed into `(x, y)` a chance to crumble.
+    fn erode(&mut self, x: usize, y: usize) {
+        for offset in NEIGHBORS.iter() {
+            let (i, j) = match self.neighbor(x, y, *offset) {
+                Some(neighbor) => neighbor,
+                None => continue,
+            };
+
+            let index = j * self.size.width + i;
+
+            if self.materials[index] != Material::Rock || !self.rng.chance(EROSION_ODDS) {
+                continue;
+            }
+
+            if self.rng.chance(2) {
+                let spread = self.default_spreads[Material::Sand as usize];
+
+                self.put(i, j, Material::Sand, self.tints[index], spread);
+            } else {
+                self.put(i, j, Material::Air, Tint::None, 0);
+            }
+        }
+    }
+
+    /// Returns how deep the gas is in the column below `(x, y)`, starting at `y`, or `None` if the
+    /// cell is neither gas nor air.
+    fn gas_depth(&self, x: usize, y: usize) -> Option<usize> {
+        match self.get(x, y).map(|material| State::from(*material)) {
+            Some(State::Gas) => (),
+            _ => return None,
+        }
+
+        let depth = (y..(y + PRESSURE_DEPTH).min(self.size.height))
+            .take_while(|&j| {
+                matches!(self.get(x, j), Some(material) if *material != Material::Air
+                    && matches!(State::from(*material), State::Gas))
+            })
+            .count();
+
+        Some(depth)
+    }
+
+    /// Picks which way a gas cell that cannot rise tries first: toward the nearest opening, or else
+    /// the side with less gas. Keeps `preference` unless one side is clearly better, so settled gas
+    /// does not slosh.
+    fn gas_pressure_direction(&self, x: usize, y: usize, preference: isize) -> isize {
+        let mut sums = [0; 2];
+        let mut columns = [0; 2];
+        let mut openings = [None; 2];
+
+        for (side, dir) in [-1, 1].iter().enumerate() {
+            for i in 1..(OPENING_REACH + 1) {
+                let column = x as isize + dir * i as isize;
+
+                if column < 0 || column >= self.size.width as isize {
+                    break;
+                }
+
+                let column = column as usize;
+
+                match self.gas_depth(column, y) {
+                    Some(depth) if i <= PRESSURE_REACH => {
+                        sums[side] += depth;
+                        columns[side] += 1;
+                    }
+                    Some(_) => (),
+                    None => break,
+                }
+
+                if y > 0 && matches!(self.get(column, y - 1), Some(Material::Air)) {
+                    openings[side] = Some(i);
+                    break;
+                }
+            }
+        }
+
+        match openings {
+            [Some(left), Some(right)] if left < right => return -1,
+            [Some(left), Some(right)] if right < left => return 1,
+            [Some(_), None] => return -1,
+            [None, Some(_)] => return 1,
+            _ => (),
+        }
+
+        if columns[0] == 0 || columns[1] == 0 {
+            return preference;
+        }
+
+        // Compare the average depth on each side without dividing.
+        let left = sums[0] * columns[1];
+        let right = sums[1] * columns[0];
+
+        if left > right {
+            1
+        } else if right > left {
+            -1
+        } else {
+            preference
+        }
+    }
+
+    /// Returns how many cells a falling solid can drop this tick.
+    fn fall_distance(&self, x: usize, y: usize) -> usize {
+        // Each substep covers its share of the distance, so nothing skips over a thin wall.
+        let limit = self.max_fall_speed.div_ceil(self.substeps);
+
+        let speed = self.velocities[y * self.size.width + x]
+            .saturating_add(1)
+            .min(self.max_fall_speed)
+            .min(limit);
+
+        let mut distance = 0;
+
+        for step in 1..=speed as usize {
+            match self.get(x, y + step) {
+                Some(material) if matches!(State::from(*material), State::Gas | State::Liquid) => {
+                    distance = step;
+                }
+                _ => break,
+            }
+        }
+
+        distance
+    }
+
+    fn neighbor(&self, x: usize, y: usize, offset: (isize, isize)) -> Option<(usize, usize)> {
+        let x = x as isize + offset.0;
+        let y = y as isize + offset.1;
+
+        if x < 0 || y < 0 || x >= self.size.width as isize || y >= self.size.height as isize {
+            return None;
+        }
+
+        // Like `get`, anything beyond the region being simulated is out of reach.
+        if !self.window.contains(x as usize, y as usize) {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+
+    /// Burns a fire down by one tick, lighting any fuse or oil it touches.
+    fn burn(&mut self, x: usize, y: usize) {
+        for offset in NEIGHBORS.iter() {
+            if let Some((i, j)) = self.neighbor(x, y, *offset) {
+                let index = j * self.size.width + i;
+
+                match self.materials[index] {
+                    Material::Fuse if self.lives[index] == 0 => {
+                        self.lives[index] = FUSE_DELAY;
+
+                        // A freshly lit fuse waits until next tick to start counting down, so a
+                        // fuse burns at the same rate in either scan direction.
+                        self.dirty.mark(index);
+
+                        self.warm_up(i, j);
+                    }
+                    Material::Oil => {
+                        self.set_material(index, Material::Fire);
+                        self.lives[index] = OIL_FIRE_LIFE;
+                        self.velocities[index] = 0;
+
+                        // Like a fuse, oil catches one cell per tick regardless of scan direction.
+                        self.dirty.mark(index);
+                        self.note_change(index);
+
+                        self.warm_up(i, j);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let index = y * self.size.width + x;
+
+        // Only oil fires start out burning longer than an ordinary flame.
+        if self.lives[index] > FIRE_LIFE && y > 0 && self.rng.chance(SMOKE_ODDS) {
+            let above = index - self.size.width;
+
+            if self.materials[above] == Material::Air {
+                self.set_material(above, Material::Smoke);
+                self.tints[above] = Tint::None;
+                self.spreads[above] = self.default_spreads[Material::Smoke as usize];
+                self.dirty.mark(above);
+                self.note_change(above);
+
+                self.warm_up(x, y - 1);
+            }
+        }
+
+        self.lives[index] = self.lives[index].saturating_sub(1);
+
+        if self.lives[index] == 0 {
+            self.set_material(index, Material::Air);
+            self.tints[index] = Tint::None;
+            self.note_change(index);
+        }
+
+        self.warm_up(x, y);
+    }
+
+    /// Counts a lit fuse down, bursting it into flame once its delay runs out.
+    fn smolder(&mut self, x: usize, y: usize) {
+        let index = y * self.size.width + x;
+
+        if self.lives[index] == 0 {
+            return;
+        }
+
+        self.lives[index] -= 1;
+
+        if self.lives[index] == 0 {
+            self.set_material(index, Material::Fire);
+            self.lives[index] = FIRE_LIFE;
+            self.dirty.mark(index);
+            self.note_change(index);
+        }
+
+        self.warm_up(x, y);
+    }
+
+    /// Occasionally spreads a virus into a random neighboring liquid or gas, and ages it.
+    fn infect(&mut self, x: usize, y: usize) {
+        if self.rng.chance(VIRUS_ODDS) {
+            let offset = NEIGHBORS[(self.rng.next() % NEIGHBORS.len() as u64) as usize];
+
+            if let Some((i, j)) = self.neighbor(x, y, offset) {
+                let susceptible = matches!(
+                    self.get(i, j).map(|material| State::from(*material)),
+                    Some(State::Liquid | State::Gas)
+                );
+
+                if susceptible {
+                    let index = j * self.size.width + i;
+
+                    self.set_material(index, Material::Virus);
+                    self.tints[index] = self.tints[y * self.size.width + x];
+                    self.lives[index] = VIRUS_LIFE;
+                    self.velocities[index] = 0;
+
+                    // Newly infected cells wait until next tick, so a virus spreads at most one
+                    // cell per tick.
+                    self.dirty.mark(index);
+                    self.note_change(index);
+
+                    self.warm_up(i, j);
+                }
+            }
+        }
+
+        let index = y * self.size.width + x;
+
+        self.lives[index] = self.lives[index].saturating_sub(1);
+
+        if self.lives[index] == 0 {
+            self.set_material(index, Material::Air);
+            self.tints[index] = Tint::None;
+            self.note_change(index);
+        }
+
+        self.warm_up(x, y);
+    }
+
+    fn flush_forecast(&mut self) {
+        // A tick in progress still needs its forecast to carry moves made so far over into the next
+        // tick, so it is only copied, not cleared.
+        let in_tick = self.tick.is_some();
+
+        // Instead of queuing chunks to be active, immediately set the chunks as active.
+        for i in self.forecast.iter() {
+            self.active_chunks.set(i, true);
+            self.active_bounds[i].include(self.forecast_bounds[i]);
+
+            if !in_tick {
+                self.forecast_bounds[i] = Bounds::EMPTY;
+            }
+
+            // Only wake the world up if something actually changed.
+            self.hot = true;
+        }
+
+        if !in_tick {
+            self.forecast.clear();
+        }
+    }
+
+    /// Grows the dirty rect of every chunk near a changed cell, by twice as far as any cell could
+    /// move.
+    fn expand_bounds(&mut self, x: usize, y: usize) {
+        let margin = 2 * self.max_spread.min(self.spread_cap) as usize + 1;
+
+        let area = Bounds {
+            left: x.saturating_sub(margin),
+            top: y.saturating_sub(margin),
+            right: x + margin,
+            bottom: y + margin,
+        };
+
+        let (cx, cy) = self.world_to_chunk(x, y);
+        let neighborhood = self.neighborhoods[cy * self.chunk_columns + cx];
+
+        for &index in neighborhood.chunks() {
+            let index = index as usize;
+            let (left, top, width, height) =
+                self.chunk_bounds(index % self.chunk_columns, index / self.chunk_columns);
+
+            let clipped = area.intersect(Bounds {
+                left,
+                top,
+                right: left + width - 1,
+                bottom: top + height - 1,
+            });
+
+            if clipped.is_empty() {
+                continue;
+            }
+
+            self.forecast_bounds[index].include(clipped);
+
+            // Let the change ripple through the rest of the current tick, like it would when
+            // scanning whole chunks.
+            if self.active_chunks.get(index) {
+                self.active_bounds[index].include(clipped);
+            }
+        }
+    }
+
+    fn warm_up(&mut self, x: usize, y: usize) {
+        if self.dirty_rects {
+            self.expand_bounds(x, y);
+        }
+
+        let index = (y / self.chunk_size) * self.chunk_columns + x / self.chunk_size;
+
+        for &chunk in self.neighborhoods[index].chunks() {
+            self.forecast.set(chunk as usize, true);
+        }
+    }
+
+    /// Paints a stroke of discs along a line. Returns the number of cells whose material changed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn paint(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        radius: usize,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_PAINT);
+
+            for value in [x1, y1, x2, y2, radius].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        }
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        stroke(self.size, x1, y1, x2, y2, radius, |x, y| {
+            changed += self.put(x, y, material, tint, spread) as u32;
+        });
+
+        self.flush_forecast();
+        self.end_edit();
+
+        changed
+    }
+
+    /// Paints a stroke whose radius tapers from `r1` at `(x1, y1)` to `r2` at `(x2, y2)`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn paint_tapered(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        r1: usize,
+        x2: usize,
+        y2: usize,
+        r2: usize,
+        material: Material,
+        tint: Tint,
+        spread: u8,
+    ) -> u32 {
+        let log = self.record(OP_PAINT_TAPERED, |log| {
+            for value in [x1, y1, r1, x2, y2, r2].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.extend_from_slice(&[material as u8, tint as u8, spread]);
+        });
+
+        let changed = if r1 == r2 {
+            self.paint(x1, y1, x2, y2, r1, material, tint, spread)
+        } else {
+            self.begin_edit();
+
+            let mut changed = 0;
+
+            capsule(self.size, x1, y1, r1, x2, y2, r2, |x, y| {
+                changed += self.put(x, y, material, tint, spread) as u32;
+            });
+
+            self.flush_forecast();
+            self.end_edit();
+
+            changed
+        };
+
+        self.resume_recording(log);
+
+        changed
+    }
+
+    /// Paints a stroke of a material using its default tint mode and spread.
+    pub fn paint_default(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        radius: usize,
+        material: Material,
+    ) -> u32 {
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_PAINT_DEFAULT);
+
+            for value in [x1, y1, x2, y2, radius].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            log.push(material as u8);
+        }
+
+        let spread = self.default_spreads[material as usize];
+        let mut changed = 0;
+
+        self.begin_edit();
+
+        stroke(self.size, x1, y1, x2, y2, radius, |x, y| {
+            let tint = self.default_tint(material);
+
+            changed += self.put(x, y, material, tint, spread) as u32;
+        });
+
+        self.flush_forecast();
+        self.end_edit();
+
+        changed
+    }
+
+    /// Paints a stroke like `paint`, picking each cell's material from `materials` weighted by
+    /// `weights`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn paint_mixed(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        radius: usize,
+        materials: &[u8],
+        weights: &[u8],
+        tint: Tint,
+        spread: u8,
+    ) -> Result<u32, String> {
+        if materials.is_empty() || materials.len() != weights.len() {
+            return Err("expected one weight for every material".to_string());
+        }
+
+        let materials = materials
+            .iter()
+            .map(|&material| Material::try_from(material))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total: u64 = weights.iter().map(|&weight| weight as u64).sum();
+
+        if total == 0 {
+            return Err("at least one weight must be greater than zero".to_string());
+        }
+
+        if let Some(log) = self.recording.as_mut() {
+            log.push(OP_PAINT_MIXED);
+
+            for value in [x1, y1, x2, y2, radius].iter() {
+                write_u32(log, *value as u32);
+            }
+
+            write_list(
+                log,
+                &materials
+                    .iter()
+                    .map(|&material| material as u8)
+                    .collect::<Vec<_>>(),
+            );
+            write_list(log, weights);
+            log.extend_from_slice(&[tint as u8, spread]);
+        }
+
+        self.begin_edit();
+
+        let mut changed = 0;
+
+        stroke(self.size, x1, y1, x2, y2, radius, |x, y| {
+            let material = if materials.len() == 1 {
+                materials[0]
+            } else {
+                let mut roll = self.rng.next() % total;
+                let mut choice = materials[0];
+
+                for (&material, &weight) in materials.iter().zip(weights) {
+                    if roll < weight as u64 {
+                        choice = material;
+
+                        break;
+                    }
+
+                    roll -= weight as u64;
+                }
+
+                choice
+            };
+
+            changed += self.put(x, y, material, tint, spread) as u32;
+        });
+
+        self.flush_forecast();
+        self.end_edit();
+
+        Ok(changed)
+    }
+
+    /// Returns the sorted linear indices of every cell that `paint` would touch, without modifying
+    /// the world.
+    pub fn preview_paint(
+        &self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        radius: usize,
+    ) -> Vec<u32> {
+        let mut indices = Vec::new();
+
+        stroke(self.size, x1, y1, x2, y2, radius, |x, y| {
+            indices.push((y * self.size.width + x) as u32);
+        });
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices
+    }
+
+    fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) -> bool {
+        let a = y1 * self.size.width + x1;
+        let b = y2 * self.size.width + x2;
+
+        if a >= self.materials.len() || b >= self.materials.len() {
+            return false;
+        }
+
+        if !self.front.is_empty() {
+            // Each cell may take part in at most one move per double-buffered tick; the first move
+            // to claim a cell wins.
+            if self.dirty.get(a) || self.dirty.get(b) {
+                return false;
+            }
+
+            self.dirty.mark(a);
+            self.dirty.mark(b);
+
+            self.materials.swap(a, b);
+            self.tints.swap(a, b);
+            self.spreads.swap(a, b);
+            self.lives.swap(a, b);
+            self.velocities.swap(a, b);
+            self.temperatures.swap(a, b);
+
+            self.record_swap(a, b);
+
+            return true;
+        }
+
+        match (
+            State::from(self.materials[a]),
+            State::from(self.materials[b]),
+        ) {
+            (State::Solid, State::Liquid)
+            | (State::Solid, State::Gas)
+            | (State::Liquid, State::Gas) => {
+                let temp_a = self.materials[a];
+                let temp_b = self.materials[b];
+
+                self.materials[a] = temp_b;
+                self.materials[b] = temp_a;
+
+                let temp_a = self.tints[a];
+                let temp_b = self.tints[b];
+
+                self.tints[a] = temp_b;
+                self.tints[b] = temp_a;
+
+                let temp_a = self.spreads[a];
+                let temp_b = self.spreads[b];
+
+                self.spreads[a] = temp_b;
+                self.spreads[b] = temp_a;
+
+                self.lives.swap(a, b);
+                self.velocities.swap(a, b);
+                self.temperatures.swap(a, b);
+
+                self.record_swap(a, b);
+
+                return true;
+            }
+            _ => (),
+        }
+
+        if self.dirty.get(a) || self.dirty.get(b) {
+            return false;
+        }
+
+        let temp_a = self.materials[a];
+        let temp_b = self.materials[b];
+
+        self.materials[a] = temp_b;
+        self.materials[b] = temp_a;
+
+        if temp_b != Material::Air {
+            self.dirty.mark(a);
+        }
+        if temp_a != Material::Air {
+            self.dirty.mark(b);
+        }
+
+        let temp_a = self.tints[a];
+        let temp_b = self.tints[b];
+
+        self.tints[a] = temp_b;
+        self.tints[b] = temp_a;
+
+        let temp_a = self.spreads[a];
+        let temp_b = self.spreads[b];
+
+        self.spreads[a] = temp_b;
+        self.spreads[b] = temp_a;
+
+        self.lives.swap(a, b);
+        self.velocities.swap(a, b);
+        self.temperatures.swap(a, b);
+
+        self.record_swap(a, b);
+
+        true
+    }
+
+    fn record_swap(&mut self, a: usize, b: usize) {
+        let (row_a, row_b) = (a / self.size.width, b / self.size.width);
+
+        // The cells have already traded places, so air that moved between rows moves the counts.
+        if row_a != row_b {
+            match (
+                self.materials[a] == Material::Air,
+                self.materials[b] == Material::Air,
+            ) {
+                (false, true) => {
+                    self.row_counts[row_a] += 1;
+                    self.row_counts[row_b] -= 1;
+                }
+                (true, false) => {
+                    self.row_counts[row_a] -= 1;
+                    self.row_counts[row_b] += 1;
+                }
+                _ => (),
+            }
+        }
+
+        self.note_change(a);
+        self.note_change(b);
+        self.moved += 1;
+
+        for &index in [a, b].iter() {
+            if !self.motion[index] {
+                self.motion[index] = true;
+                self.moving.push(index as u32);
+            }
+        }
+
+        #[cfg(feature = "debug-checks")]
+        for &index in [a, b].iter() {
+            if self.materials[index] != Material::Air {
+                let (cx, cy) =
+                    self.world_to_chunk(index % self.size.width, index / self.size.width);
+
+                self.moved_chunks[cy * self.chunk_columns + cx] = true;
+            }
+        }
+
+        if !self.color_overrides.is_empty() {
+            let color_a = self.color_overrides.remove(&a);
+            let color_b = self.color_overrides.remove(&b);
+
+            // The cells have already traded places, so each takes the other's color.
+            if let Some(rgba) = color_a {
+                self.color_overrides.insert(b, rgba);
+            }
+
+            if let Some(rgba) = color_b {
+                self.color_overrides.insert(a, rgba);
+            }
+        }
+
+        if self.erosion && self.materials[b] == Material::Water {
+            self.erode(b % self.size.width, b / self.size.width);
+        }
+
+        if !self.chunk_stats.is_empty() {
+            let (cx, cy) = self.world_to_chunk(a % self.size.width, a / self.size.width);
+            let index = cy * self.chunk_columns + cx;
+
+            self.chunk_stats[index] = self.chunk_stats[index].saturating_add(1);
+        }
+
+        if let Some(log) = self.debug_log.as_mut() {
+            log.swaps.push(a as u32);
+            log.swaps.push(b as u32);
+        }
+    }
+
+    /// Runs up to `n` ticks, stopping early once the world settles. Returns the number of ticks
+    /// that ran.
+    pub fn step(&mut self, n: u32) -> u32 {
+        for i in 0..n {
+            if !self.hot {
+                return i;
+            }
+
+            self.simulate();
+        }
+
+        n
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    pub fn simulate(&mut self) -> TickReport {
+        self.simulate_all()
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn simulate(&mut self) -> TickReport {
+        self.scanned = 0;
+        self.timings = TickTimings::default();
+
+        let stopwatch = Stopwatch::start();
+        let report = self.simulate_all();
+
+        self.timings.bookkeeping_micros =
+            stopwatch.elapsed_micros() - self.timings.scan_micros - self.timings.movement_micros;
+
+        self.stats = SimStats {
+            moved: report.moved_cells as usize,
+            cells_scanned: self.scanned,
+            chunks_active: report.active_chunks as usize,
+        };
+
+        report
+    }
+
+    /// Returns the counters from the last call to `simulate`.
+    #[cfg(feature = "profiling")]
+    pub fn sim_stats(&self) -> SimStats {
+        self.stats
+    }
+
+    /// Returns where the time went during the last call to `simulate`.
+    #[cfg(feature = "profiling")]
+    pub fn tick_timings(&self) -> TickTimings {
+        self.timings
+    }
+
+    fn simulate_all(&mut self) -> TickReport {
+        let log = self.record(OP_SIMULATE, |_| ());
+        let report = self.simulate_chunks(0, 0, self.chunk_columns, self.chunk_rows);
+
+        self.resume_recording_with_hash(log);
+
+        report
+    }
+
+    /// Runs a tick over only the chunks that overlap the given rectangle, whose border behaves like
+    /// the edge of the world.
+    pub fn simulate_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> TickReport {
+        let right = x.saturating_add(width).min(self.size.width);
+        let bottom = y.saturating_add(height).min(self.size.height);
+
+        if x >= right || y >= bottom {
+            return TickReport {
+                moved_cells: 0,
+                active_chunks: 0,
+                still_hot: self.hot,
+            };
+        }
+
+        let log = self.record(OP_SIMULATE_REGION, |log| {
+            for value in [x, y, width, height].iter() {
+                write_u32(log, *value as u32);
+            }
+        });
+
+        let (left, top) = self.world_to_chunk(x, y);
+        let (right, bottom) = self.world_to_chunk(right - 1, bottom - 1);
+        let report = self.simulate_chunks(left, top, right + 1, bottom + 1);
+
+        self.resume_recording_with_hash(log);
+
+        report
+    }
+
+    /// Runs a tick over the chunks from `(left, top)` to `(right, bottom)`, exclusive, split into
+    /// substeps.
+    fn simulate_chunks(
+        &mut self,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+    ) -> TickReport {
+        if self.tick.is_none() {
+            self.clear_motion();
+        }
+
+        let mut report = self.simulate_pass(left, top, right, bottom);
+
+        for _ in 1..self.substeps {
+            if !report.still_hot {
+                break;
+            }
+
+            // Keep collecting changes on top of the previous pass, rather than starting over.
+            self.changes_stale = false;
+
+            let pass = self.simulate_pass(left, top, right, bottom);
+
+            report = TickReport {
+                moved_cells: report.moved_cells + pass.moved_cells,
+                active_chunks: report.active_chunks + pass.active_chunks,
+                still_hot: pass.still_hot,
+            };
+        }
+
+        self.moved = report.moved_cells;
+        self.changes_stale = true;
+
+        self.flush_cell_events();
+        self.capture_rewind();
+
+        report
+    }
+
+    /// Runs a single pass over the chunks from `(left, top)` to `(right, bottom)`, exclusive.
+    fn simulate_pass(
+        &mut self,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+    ) -> TickReport {
+        if self.tick.is_none() && !self.begin_tick(left, top, right, bottom) {
+            // Time passes even when nothing moves.
+            self.ticks += 1;
+            self.list_touched_chunks();
+
+            return TickReport {
+                moved_cells: 0,
+                active_chunks: 0,
+                still_hot: self.hot,
+            };
+        }
+
+        #[cfg(feature = "profiling")]
+        let (stopwatch, movement) = (Stopwatch::start(), self.timings.movement_micros);
+
+        // Only a whole tick of the whole world is striped.
+        let whole = self.tick.is_some_and(|tick| {
+            tick.row == 0
+                && (tick.left, tick.top, tick.right, tick.bottom)
+                    == (0, 0, self.chunk_columns, self.chunk_rows)
+        });
+
+        if self.stripe_rows > 0 && whole {
+            self.simulate_stripes();
+        }
+
+        while self.advance() {}
+
+        // The time spent moving cells was counted as the scan went along.
+        #[cfg(feature = "profiling")]
+        {
+            self.timings.scan_micros +=
+                stopwatch.elapsed_micros() - (self.timings.movement_micros - movement);
+        }
+
+        self.end_tick()
+    }
+
+    /// Processes every row of the current tick in stripes, phase by phase. See `set_stripe_rows`.
+    fn simulate_stripes(&mut self) {
+        let columns = self.chunk_columns;
+        let tops: Vec<usize> = (0..self.chunk_rows).step_by(self.stripe_rows).collect();
+
+        for phase in 0..2 {
+            // A stripe with nothing awake in it has nothing to do.
+            let tops: Vec<usize> = tops
+                .iter()
+                .skip(phase)
+                .step_by(2)
+                .copied()
+                .filter(|&top| {
+                    let bottom = (top + self.stripe_rows).min(self.chunk_rows);
+
+                    self.active_chunks.any_in(top * columns, bottom * columns)
+                })
+                .collect();
+
+            if tops.is_empty() {
+                continue;
+            }
+
+            // Each stripe gets an equal share of the room left under the particle limit, so the
+            // stripes of a phase cannot overrun it together.
+            let share =
+                self.particle_limit.saturating_sub(self.particle_count()) / tops.len() as u32;
+
+            let mut stripes: Vec<Stripe> = tops
+                .iter()
+                .filter_map(|&top| self.split_stripe(top, share))
+                .collect();
+
+            #[cfg(feature = "parallel")]
+            stripes.par_iter_mut().for_each(Stripe::run);
+
+            #[cfg(not(feature = "parallel"))]
+            stripes.iter_mut().for_each(Stripe::run);
+
+            for stripe in stripes {
+                self.merge_stripe(stripe);
+            }
+        }
+
+        if let Some(tick) = self.tick.as_mut() {
+            tick.row = self.window.bottom - self.window.top + 1;
+        }
+    }
+
+    /// Copies the stripe starting at chunk row `top` onto a world of its own, along with the chunk
+    /// row to either side of it. Only the stripe's own chunks are awake, so only its own rows are
+    /// scanned. Returns nothing if the stripe lies outside of the tick's window.
+    fn split_stripe(&mut self, top: usize, share: u32) -> Option<Stripe> {
+        let width = self.size.width;
+        let bottom = (top + self.stripe_rows).min(self.chunk_rows);
+
+        let offset = top.saturating_sub(1) * self.chunk_size;
+        let end = ((bottom + 1) * self.chunk_size).min(self.size.height);
+
+        let own = Bounds {
+            left: 0,
+            top: top * self.chunk_size,
+            right: width - 1,
+            bottom: (bottom * self.chunk_size).min(self.size.height) - 1,
+        };
+
+        if self.window.intersect(own).is_empty() {
+            return None;
+        }
+
+        let cells = offset * width..end * width;
+        let mut world = World::create(width, end - offset, self.chunk_size);
+
+        world
+            .materials
+            .copy_from_slice(&self.materials[cells.clone()]);
+        world.tints.copy_from_slice(&self.tints[cells.clone()]);
+        world.spreads.copy_from_slice(&self.spreads[cells.clone()]);
+        world.lives.copy_from_slice(&self.lives[cells.clone()]);
+        world
+            .velocities
+            .copy_from_slice(&self.velocities[cells.clone()]);
+        world
+            .temperatures
+            .copy_from_slice(&self.temperatures[cells.clone()]);
+        world.recount();
+
+        world.color_overrides = self
+            .color_overrides
+            .iter()
+            .filter(|(index, _)| cells.contains(index))
+            .map(|(&index, &rgba)| (index - cells.start, rgba))
+            .collect();
+
+        // Whatever moved into these rows in the first phase stays put in the second.
+        for index in cells.clone() {
+            if self.dirty.get(index) {
+                world.dirty.mark(index - cells.start);
+            }
+        }
+
+        if !self.front.is_empty() {
+            world.front = self.front[cells.clone()].to_vec();
+        }
+
+        let first = offset / self.chunk_size;
+
+        for i in top * self.chunk_columns..bottom * self.chunk_columns {
+            let local = i - first * self.chunk_columns;
+
+            world.active_chunks.set(local, self.active_chunks.get(i));
+            world.active_bounds[local] = self.active_bounds[i].raised(offset);
+        }
+
+        if !self.chunk_stats.is_empty() {
+            world.chunk_stats = vec![0; world.chunk_columns * world.chunk_rows];
+        }
+
+        world.particle_limit = world.particle_count().saturating_add(share);
+        world.dirty_rects = self.dirty_rects;
+        world.max_spread = self.max_spread;
+        world.spread_cap = self.spread_cap;
+        world.flow_bias = self.flow_bias;
+        world.ticks = self.ticks;
+        world.ambient_temperature = self.ambient_temperature;
+        world.evaporation = self.evaporation;
+        world.erosion = self.erosion;
+        world.surface_tension = self.surface_tension;
+        world.update_mode = self.update_mode;
+        world.default_spreads = self.default_spreads;
+        world.default_tints = self.default_tints;
+        world.max_fall_speed = self.max_fall_speed;
+        world.substeps = self.substeps;
+        world.tick_rate_divisor = self.tick_rate_divisor;
+        world.paused = self.paused;
+        world.rng = Rng::new(self.rng.next());
+
+        world.window = Bounds {
+            left: self.window.left,
+            top: self.window.top.max(offset) - offset,
+            right: self.window.right,
+            bottom: self.window.bottom.min(end - 1) - offset,
+        };
+        world.tick = Some(Tick {
+            left: 0,
+            top: 0,
+            right: world.chunk_columns,
+            bottom: world.chunk_rows,
+            row: 0,
+        });
+
+        Some(Stripe {
+            counts: world.counts,
+            world,
+            offset,
+        })
+    }
+
+    /// Copies a stripe that has run back into the world, along with everything it did.
+    fn merge_stripe(&mut self, stripe: Stripe) {
+        let Stripe {
+            world,
+            offset,
+            counts,
+        } = stripe;
+
+        let start = offset * self.size.width;
+        let cells = start..start + world.materials.len();
+
+        self.materials[cells.clone()].copy_from_slice(&world.materials);
+        self.tints[cells.clone()].copy_from_slice(&world.tints);
+        self.spreads[cells.clone()].copy_from_slice(&world.spreads);
+        self.lives[cells.clone()].copy_from_slice(&world.lives);
+        self.velocities[cells.clone()].copy_from_slice(&world.velocities);
+        self.temperatures[cells.clone()].copy_from_slice(&world.temperatures);
+        self.row_counts[offset..offset + world.size.height].copy_from_slice(&world.row_counts);
+
+        for (count, (&after, &before)) in self
+            .counts
+            .iter_mut()
+            .zip(world.counts.iter().zip(counts.iter()))
+        {
+            *count = count.wrapping_add(after).wrapping_sub(before);
+        }
+
+        self.color_overrides
+            .retain(|index, _| !cells.contains(index));
+        self.color_overrides.extend(
+            world
+                .color_overrides
+                .iter()
+                .map(|(&index, &rgba)| (index + start, rgba)),
+        );
+
+        for index in 0..world.materials.len() {
+            if world.dirty.get(index) {
+                self.dirty.mark(start + index);
+            }
+        }
+
+        for &index in world.changes.iter() {
+            self.note_change(start + index as usize);
+        }
+
+        for &index in world.moving.iter() {
+            let index = start + index as usize;
+
+            if !self.motion[index] {
+                self.motion[index] = true;
+                self.moving.push(index as u32);
+            }
+        }
+
+        self.moved += world.moved;
+        self.max_spread = self.max_spread.max(world.max_spread);
+
+        let first = offset / self.chunk_size;
+        let base = first * self.chunk_columns;
+        let last = world.chunk_rows - 1;
+
+        for i in world.forecast.iter() {
+            let row = i / self.chunk_columns;
+
+            // A chunk woken along the edge of the stripe's world may have neighbors beyond it.
+            if (row == 0 && first > 0) || (row == last && first + last + 1 < self.chunk_rows) {
+                for &chunk in self.neighborhoods[base + i].chunks() {
+                    self.forecast.set(chunk as usize, true);
+                }
+            }
+
+            self.forecast.set(base + i, true);
+        }
+
+        for (i, bounds) in world.forecast_bounds.iter().enumerate() {
+            if bounds.is_empty() {
+                continue;
+            }
+
+            let bounds = bounds.lowered(offset);
+
+            self.forecast_bounds[base + i].include(bounds);
+
+            // Let the changes ripple through the stripes still to come this tick.
+            if self.active_chunks.get(base + i) {
+                self.active_bounds[base + i].include(bounds);
+            }
+        }
+
+        for (i, &moves) in world.chunk_stats.iter().enumerate() {
+            self.chunk_stats[base + i] = self.chunk_stats[base + i].saturating_add(moves);
+        }
+
+        #[cfg(feature = "debug-checks")]
+        for (i, &moved) in world.moved_chunks.iter().enumerate() {
+            self.moved_chunks[base + i] |= moved;
+        }
+
+        #[cfg(feature = "profiling")]
+        {
+            self.scanned += world.scanned;
+        }
+    }
+
+    /// Works through the current tick until roughly `max_micros` have passed. Returns true once a
+    /// full tick has completed.
+    pub fn simulate_budgeted(&mut self, max_micros: u32) -> bool {
+        let log = self.record(OP_SIMULATE_BUDGETED, |_| ());
+        let stopwatch = Stopwatch::start();
+        let mut rows: u32 = 0;
+
+        let complete = self.simulate_rows(|| {
+            rows += 1;
+            stopwatch.elapsed_micros() < max_micros as f64
+        });
+
+        // The time a row takes differs between runs, so a replay goes by the number of rows.
+        let log = log.map(|mut log| {
+            write_u32(&mut log, rows);
+            log.push(complete as u8);
+            log
+        });
+
+        self.resume_recording_with_hash(log);
+
+        complete
+    }
+
+    /// Works through rows of the current tick while `more` allows. Returns whether the tick
+    /// completed.
+    fn simulate_rows(&mut self, mut more: impl FnMut() -> bool) -> bool {
+        if self.tick.is_none() {
+            self.clear_motion();
+        }
+
+        if self.tick.is_none() && !self.begin_tick(0, 0, self.chunk_columns, self.chunk_rows) {
+            self.ticks += 1;
+            self.list_touched_chunks();
+            self.flush_cell_events();
+            self.capture_rewind();
+
+            return true;
+        }
+
+        while self.advance() {
+            if self.remaining_rows() > 0 && !more() {
+                return false;
+            }
+        }
+
+        self.end_tick();
+        self.flush_cell_events();
+        self.capture_rewind();
+
+        true
+    }
+
+    /// Processes a single row of chunks of the current tick, reporting what happened along the way.
+    pub fn debug_step(&mut self) -> DebugStepInfo {
+        let log = self.record(OP_DEBUG_STEP, |_| ());
+        let info = self.step_chunk_row();
+
+        self.resume_recording_with_hash(log);
+
+        info
+    }
+
+    fn step_chunk_row(&mut self) -> DebugStepInfo {
+        let mut info = DebugStepInfo {
+            chunk_row: 0,
+            visited: Vec::new(),
+            swaps: Vec::new(),
+            complete: true,
+        };
+
+        if self.tick.is_none() {
+            self.clear_motion();
+        }
+
+        if self.tick.is_none() && !self.begin_tick(0, 0, self.chunk_columns, self.chunk_rows) {
+            self.ticks += 1;
+            self.list_touched_chunks();
+            self.flush_cell_events();
+            self.capture_rewind();
+
+            return info;
+        }
+
+        self.debug_log = Some(DebugLog::default());
+
+        let mut chunk_row = None;
+
+        while let Some(row) = self.tick.map(|tick| tick.row) {
+            if self.remaining_rows() == 0 {
+                break;
+            }
+
+            let current = self.row_to_y(row) / self.chunk_size;
+
+            if *chunk_row.get_or_insert(current) != current {
+                break;
+            }
+
+            self.advance();
+        }
+
+        if let Some(log) = self.debug_log.take() {
+            info.visited = log.visited;
+            info.swaps = log.swaps;
+        }
+
+        info.chunk_row = chunk_row.unwrap_or(0);
+        info.complete = self.remaining_rows() == 0;
+
+        if info.complete {
+            self.end_tick();
+            self.flush_cell_events();
+            self.capture_rewind();
+        }
+
+        info
+    }
+
+    /// Counts off a finished tick, taking a snapshot for `rewind` once enough have passed.
+    fn capture_rewind(&mut self) {
+        let due = match self.rewind.as_mut() {
+            Some(rewind) => {
+                rewind.elapsed += 1;
+                rewind.elapsed >= rewind.interval
+            }
+            None => false,
+        };
+
+        if !due {
+            return;
+        }
+
+        let frame = RewindFrame {
+            ticks: self.ticks,
+            rng: self.rng.state,
+            packed: self.snapshot().compress(),
+        };
+
+        if let Some(rewind) = self.rewind.as_mut() {
+            if rewind.frames.len() == rewind.capacity {
+                rewind.frames.pop_front();
+            }
+
+            rewind.frames.push_back(frame);
+            rewind.elapsed = 0;
+        }
+    }
+
+    /// Finishes a tick left unfinished by `simulate_budgeted` or `debug_step`, if there is one.
+    fn finish_tick(&mut self) {
+        if self.tick.is_some() {
+            while self.advance() {}
+
+            self.end_tick();
+        }
+    }
+
+    /// Prepares a tick over the chunks from `(left, top)` to `(right, bottom)`, exclusive. Returns
+    /// false if there is nothing to simulate.
+    fn begin_tick(&mut self, left: usize, top: usize, right: usize, bottom: usize) -> bool {
+        if self.changes_stale {
+            self.clear_changes();
+        }
+
+        self.moved = 0;
+        self.active_cells = 0;
+
+        if !self.hot || self.materials.is_empty() {
+            // Whatever changes next belongs to the next frame.
+            self.changes_stale = true;
+
+            return false;
+        }
+
+        let window = Bounds {
+            left: left * self.chunk_size,
+            top: top * self.chunk_size,
+            right: (right * self.chunk_size).min(self.size.width) - 1,
+            bottom: (bottom * self.chunk_size).min(self.size.height) - 1,
+        }
+        .intersect(self.simulation_bounds);
+
+        if window.is_empty() {
+            self.changes_stale = true;
+
+            return false;
+        }
+
+        self.window = window;
+
+        // Material that settled against the edge of an earlier region may be free to move now.
+        for i in 0..self.fenced.len() {
+            let (cx, cy) = (i % self.chunk_columns, i / self.chunk_columns);
+
+            if self.fenced.get(i) && !self.on_region_edge(cx, cy, left, top, right, bottom) {
+                self.fenced.set(i, false);
+                self.active_chunks.set(i, true);
+                self.active_bounds[i] = Bounds::ALL;
+            }
+        }
+
+        self.defer_excess_chunks(left, top, right, bottom);
+
+        #[cfg(feature = "debug-checks")]
+        for entry in self.moved_chunks.iter_mut() {
+            *entry = false;
+        }
 
-    fn get(&self, x: usize, y: usize) -> Option<&Material> {
-        self.materials.get(y * self.size.width + x)
-    }
+        for entry in self.chunk_stats.iter_mut() {
+            *entry = 0;
+        }
 
-    fn get_chunk_index(&self, x: usize, y: usize) -> Option<usize> {
-        let x = x / self.chunk_size;
-        let y = y / self.chunk_size;
+        if self.update_mode == UpdateMode::DoubleBuffered {
+            self.front.clear();
+            self.front.extend_from_slice(&self.materials);
+        }
 
-        let index = y * self.chunk_columns + x;
+        self.tick = Some(Tick {
+            left,
+            top,
+            right,
+            bottom,
+            row: 0,
+        });
 
-        self.active_chunks.get(index).map(|_| index)
+        true
     }
 
-    pub fn reset(&mut self) {
-        for i in 0..self.materials.len() {
-            self.materials[i] = Material::Air;
-            self.tints[i] = Tint::None;
-            self.spreads[i] = 0;
+    /// Whether a chunk lies along a side of the region that is not also the edge of the world.
+    fn on_region_edge(
+        &self,
+        cx: usize,
+        cy: usize,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+    ) -> bool {
+        if cx < left || cx >= right || cy < top || cy >= bottom {
+            return false;
         }
 
-        self.hot = false;
+        (cx == left && left > 0)
+            || (cx + 1 == right && right < self.chunk_columns)
+            || (cy == top && top > 0)
+            || (cy + 1 == bottom && bottom < self.chunk_rows)
+    }
 
-        for i in 0..self.active_chunks.len() {
-            self.active_chunks[i] = false;
-            self.forecast[i] = false;
+    fn remaining_rows(&self) -> usize {
+        match self.tick {
+            Some(tick) => (self.window.bottom - self.window.top + 1).saturating_sub(tick.row),
+            None => 0,
         }
     }
 
-    pub fn place(&mut self, x: usize, y: usize, material: Material, tint: Tint, spread: u8) {
-        let index = y * self.size.width + x;
-
-        if index >= self.materials.len() {
-            return;
+    /// Processes the next row of the current tick. Returns false once every row has been
+    /// processed.
+    fn advance(&mut self) -> bool {
+        if self.remaining_rows() == 0 {
+            return false;
         }
 
-        self.materials[index] = material;
-        self.tints[index] = tint;
-        self.spreads[index] = spread;
+        if let Some(tick) = self.tick.as_mut() {
+            let row = tick.row;
 
-        self.dirty[index] = true;
+            tick.row += 1;
 
-        self.warm_up(x, y);
+            self.process_row(row);
+        }
 
-        // Instead of queuing chunks to be active, immediately set the chunks as active.
-        for (i, entry) in self.forecast.iter_mut().enumerate() {
-            if *entry {
-                self.active_chunks[i] = true;
+        true
+    }
 
-                *entry = false;
-            }
+    /// Maps a row of the current tick, counted in scan order, to its y coordinate.
+    fn row_to_y(&self, row: usize) -> usize {
+        // Double-buffered ticks scan top-down, so material from above claims contested cells first.
+        // In place, scanning bottom-up lets falling material make room for what is above it.
+        if !self.front.is_empty() {
+            self.window.top + row
+        } else {
+            self.window.bottom - row
         }
-
-        self.hot = true;
     }
 
-    fn warm_up(&mut self, x: usize, y: usize) {
-        let x = x / self.chunk_size;
-        let y = y / self.chunk_size;
+    /// Counts how many cells past `(x, y)` can be skipped before reaching the dirty rect of its
+    /// chunk.
+    fn skip_outside_bounds(&self, index: usize, x: usize, y: usize, preference: isize) -> usize {
+        let bounds = self.active_bounds[index];
+        let on_row = y >= bounds.top && y <= bounds.bottom;
+        let offset = x % self.chunk_size;
+
+        if preference < 0 {
+            if on_row && x > bounds.right {
+                (x - bounds.right - 1).min(offset)
+            } else {
+                offset
+            }
+        } else {
+            let rest = self.chunk_size - 1 - offset;
+
+            if on_row && x < bounds.left {
+                (bounds.left - x - 1).min(rest)
+            } else {
+                rest
+            }
+        }
+    }
 
-        let base = y * self.chunk_columns + x;
+    fn process_row(&mut self, row: usize) {
+        let columns = self.window.right - self.window.left + 1;
 
-        let index = base;
+        let y = self.row_to_y(row);
 
-        if let Some(target) = self.forecast.get_mut(index) {
-            *target = true;
-        }
+        let preference: isize = if y.is_multiple_of(2) { 1 } else { -1 };
+        let resting = !self.ticks.is_multiple_of(self.tick_rate_divisor as u64);
 
-        if x > 0 {
-            let index = base - 1;
+        // A row that only passes through sleeping chunks has nothing to scan.
+        let row_start = (y / self.chunk_size) * self.chunk_columns;
 
-            if let Some(target) = self.forecast.get_mut(index) {
-                *target = true;
-            }
+        if !self.active_chunks.any_in(
+            row_start + self.window.left / self.chunk_size,
+            row_start + self.window.right / self.chunk_size + 1,
+        ) {
+            return;
         }
 
-        if x < self.chunk_columns - 1 {
-            let index = base + 1;
-
-            if let Some(target) = self.forecast.get_mut(index) {
-                *target = true;
-            }
+        // Neither does a row with nothing but air in it.
+        if self.row_counts[y] == 0 {
+            return;
         }
 
-        if y > 0 {
-            let index = base - self.chunk_columns;
+        let mut column = 0;
 
-            if let Some(target) = self.forecast.get_mut(index) {
-                *target = true;
-            }
+        while column < columns {
+            let x = if preference < 0 {
+                self.window.right - column
+            } else {
+                self.window.left + column
+            };
 
-            if x > 0 {
-                let index = base - self.chunk_columns - 1;
+            // Air never does anything on its own, so leap over the whole run of it.
+            if self.materials[y * self.size.width + x] == Material::Air {
+                column += self.air_run(x, y, preference, columns - column);
 
-                if let Some(target) = self.forecast.get_mut(index) {
-                    *target = true;
-                }
+                continue;
             }
 
-            if x < self.chunk_columns - 1 {
-                let index = base - self.chunk_columns + 1;
+            column += 1;
 
-                if let Some(target) = self.forecast.get_mut(index) {
-                    *target = true;
-                }
+            if self.dirty.get(y * self.size.width + x) {
+                continue;
             }
-        }
 
-        if y < self.chunk_columns - 1 {
-            let index = base + self.chunk_columns;
+            if let Some(index) = self.get_chunk_index(x, y) {
+                if !self.active_chunks.get(index) {
+                    // Nothing in a sleeping chunk moves, so skip straight past the rest of it.
+                    column += if preference < 0 {
+                        x % self.chunk_size
+                    } else {
+                        self.chunk_size - 1 - x % self.chunk_size
+                    };
 
-            if let Some(target) = self.forecast.get_mut(index) {
-                *target = true;
-            }
+                    continue;
+                }
 
-            if x > 0 {
-                let index = base + self.chunk_columns - 1;
+                if self.dirty_rects && !self.active_bounds[index].contains(x, y) {
+                    column += self.skip_outside_bounds(index, x, y, preference);
 
-                if let Some(target) = self.forecast.get_mut(index) {
-                    *target = true;
+                    continue;
                 }
+            } else {
+                // I do not think this will ever be reached, but you can never be too safe!
+                continue;
             }
 
-            if x < self.chunk_columns - 1 {
-                let index = base + self.chunk_columns + 1;
+            #[cfg(feature = "profiling")]
+            {
+                self.scanned += 1;
+            }
 
-                if let Some(target) = self.forecast.get_mut(index) {
-                    *target = true;
-                }
+            if let Some(log) = self.debug_log.as_mut() {
+                log.visited.push((y * self.size.width + x) as u32);
             }
-        }
-    }
 
-    pub fn paint(
-        &mut self,
-        x1: usize,
-        y1: usize,
-        x2: usize,
-        y2: usize,
-        radius: usize,
-        material: Material,
-        tint: Tint,
-        spread: u8,
-    ) {
-        let x1 = x1 as isize;
-        let y1 = y1 as isize;
-        let x2 = x2 as isize;
-        let y2 = y2 as isize;
-        let radius = radius as isize;
+            let material = self.materials[y * self.size.width + x];
 
-        let dx = x2 - x1;
-        let dy = y2 - y1;
+            // Paused materials still get pushed around, they just never move on their own.
+            if self.paused[material as usize] {
+                continue;
+            }
 
-        const LEEWAY: isize = 1;
+            let behavior = material.props().behavior;
 
-        if -LEEWAY <= dx && dx <= LEEWAY {
-            let range = dy.abs() as usize;
-            let range = range.max(1);
-            let range = range.min(self.size.height);
+            #[cfg(feature = "profiling")]
+            let stopwatch = Stopwatch::start();
 
-            let mut x = x1;
-            let mut y = y1;
+            // On a tick held back by the tick rate divisor, anything that would move stays awake
+            // for the next tick that moves instead.
+            if resting && matches!(behavior, Behavior::Powder | Behavior::Gas) {
+                self.warm_up(x, y);
 
-            if y2 < y1 {
-                x = x2;
-                y = y2;
+                continue;
             }
 
-            for i in 0..range {
-                let y = y + i as isize;
+            (|| match behavior {
+                Behavior::Powder => {
+                    let distance = self.fall_distance(x, y);
 
-                for j in (y - radius)..(y + radius + 1) {
-                    if j < 0 || j > self.size.height as isize - 1 {
-                        continue;
-                    }
+                    if distance > 0 && self.swap(x, y, x, y + distance) {
+                        let index = (y + distance) * self.size.width + x;
 
-                    for i in (x - radius)..(x + radius + 1) {
-                        if i < 0 || i > self.size.width as isize - 1 {
-                            continue;
+                        self.velocities[index] =
+                            (self.velocities[index] + 1).min(self.max_fall_speed);
+
+                        // A long fall can leave the chunk it started in behind.
+                        if distance > 1 {
+                            self.warm_up(x, y);
                         }
 
-                        let distance = distance(x as f32, y as f32, i as f32, j as f32).ceil();
+                        self.warm_up(x, y + distance);
 
-                        if distance <= radius as f32 {
-                            self.place(i as usize, j as usize, material, tint, spread);
-                        }
+                        return;
                     }
+
+                    // Anything that is not in free fall has landed.
+                    self.velocities[y * self.size.width + x] = 0;
+
+                    self.move_particle(x, y, preference, false, &Movement::POWDER);
                 }
-            }
 
-            return;
-        }
+                Behavior::Liquid => {
+                    if material == Material::Water && (self.evaporate(x, y) || self.freeze(x, y)) {
+                        return;
+                    }
 
-        let slope = dy as f32 / dx as f32;
-        let y_intercept = y1 as f32 - slope * x1 as f32;
+                    if material == Material::Acid && self.corrode(x, y) {
+                        return;
+                    }
 
-        let domain = dx.abs() as usize;
-        let domain = domain.max(1);
-        let domain = domain.min(self.size.width);
+                    if resting {
+                        self.warm_up(x, y);
 
-        let leftmost = x1.min(x2) as f32;
+                        return;
+                    }
 
-        const STEP: f32 = 0.5;
-        let domain = (domain as f32 / STEP).ceil() as usize;
+                    if let Some(other) = self.get(x, y + 1) {
+                        if material.sinks_into(*other) && self.swap(x, y, x, y + 1) {
+                            self.warm_up(x, y + 1);
 
-        for i in 0..domain {
-            let x = leftmost + i as f32 * STEP;
-            let y = (((slope * x).ceil()) + y_intercept) as isize;
-            let x = x as isize;
+                            return;
+                        }
+                    }
 
-            for j in (y - radius)..(y + radius + 1) {
-                if j < 0 || j > self.size.height as isize - 1 {
-                    continue;
+                    match self.flow_lean() {
+                        Some(side) => self.move_particle(x, y, side, true, &Movement::LIQUID),
+                        None => self.move_particle(x, y, preference, false, &Movement::LIQUID),
+                    }
                 }
 
-                for i in (x - radius)..(x + radius + 1) {
-                    if i < 0 || i > self.size.width as isize - 1 {
-                        continue;
+                Behavior::Gas => {
+                    if let Some(&above) = y.checked_sub(1).and_then(|above| self.get(x, above)) {
+                        // Trading places with a denser gas marks both cells dirty, so the pair
+                        // cannot swap straight back within the same tick.
+                        if material.rises_through(above) && self.swap(x, y, x, y - 1) {
+                            self.warm_up(x, y - 1);
+                            return;
+                        }
                     }
 
-                    let distance = distance(x as f32, y as f32, i as f32, j as f32).ceil();
+                    let preference = self.gas_pressure_direction(x, y, preference);
 
-                    if distance <= radius as f32 {
-                        self.place(i as usize, j as usize, material, tint, spread);
-                    }
+                    self.move_particle(x, y, preference, false, &Movement::GAS);
                 }
+                Behavior::Burn => self.burn(x, y),
+                Behavior::Smolder => self.smolder(x, y),
+                Behavior::Infect => self.infect(x, y),
+                Behavior::Melt => self.melt(x, y),
+                Behavior::Static => (),
+            })();
+
+            #[cfg(feature = "profiling")]
+            {
+                self.timings.movement_micros += stopwatch.elapsed_micros();
             }
         }
     }
 
-    fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) -> bool {
-        let a = y1 * self.size.width + x1;
-        let b = y2 * self.size.width + x2;
-
-        if a > self.materials.len() || b > self.materials.len() {
-            return false;
+    /// Returns the side a liquid cell leans towards this time, if any.
+    fn flow_lean(&mut self) -> Option<isize> {
+        if self.flow_bias == 0 {
+            return None;
         }
 
-        match (
-            State::from(self.materials[a]),
-            State::from(self.materials[b]),
-        ) {
-            (State::Solid, State::Liquid)
-            | (State::Solid, State::Gas)
-            | (State::Liquid, State::Gas) => {
-                let temp_a = self.materials[a];
-                let temp_b = self.materials[b];
+        if self.rng.next() % 128 < self.flow_bias.unsigned_abs() as u64 {
+            Some(self.flow_bias.signum() as isize)
+        } else {
+            None
+        }
+    }
 
-                self.materials[a] = temp_b;
-                self.materials[b] = temp_a;
+    /// Moves a cell that could not move straight up or down, first diagonally, then sideways if its
+    /// movement allows.
+    fn move_particle(
+        &mut self,
+        x: usize,
+        y: usize,
+        preference: isize,
+        lean: bool,
+        movement: &Movement,
+    ) {
+        let material = self.materials[y * self.size.width + x];
+        let spread = self.spreads[y * self.size.width + x].min(self.spread_cap);
+        let ahead = y as isize + movement.gravity;
+
+        // A lone droplet has nothing to cling to, so surface tension only holds back water that
+        // is touching more water.
+        let tense = self.surface_tension
+            && material == Material::Water
+            && self.water_neighbors(x, y, (x, y)) > 0;
+
+        let slipped = self.search_sideways(x, preference, spread, lean, |world, index| {
+            match world.get(index, y) {
+                Some(other) if (movement.clears)(material, *other) => (),
+                _ => return Step::Blocked,
+            }
 
-                let temp_a = self.tints[a];
-                let temp_b = self.tints[b];
+            if ahead < 0 {
+                return Step::Blocked;
+            }
 
-                self.tints[a] = temp_b;
-                self.tints[b] = temp_a;
+            let ahead = ahead as usize;
 
-                let temp_a = self.spreads[a];
-                let temp_b = self.spreads[b];
+            match world.get(index, ahead) {
+                Some(other) if *other == material => Step::Open,
+                Some(_)
+                    if tense && world.water_neighbors(index, ahead, (x, y)) < TENSION_NEIGHBORS =>
+                {
+                    Step::Open
+                }
+                Some(other) if (movement.enters)(material, *other) => {
+                    if world.swap(x, y, index, ahead) {
+                        world.warm_up(x, ahead);
 
-                self.spreads[a] = temp_b;
-                self.spreads[b] = temp_a;
+                        return Step::Moved;
+                    }
 
-                return true;
+                    Step::Open
+                }
+                _ => Step::Blocked,
             }
-            _ => (),
-        }
+        });
 
-        if self.dirty[a] || self.dirty[b] {
-            return false;
+        if slipped {
+            return;
         }
 
-        let temp_a = self.materials[a];
-        let temp_b = self.materials[b];
+        if let Some(slides) = movement.slides {
+            self.search_sideways(x, preference, spread, lean, |world, index| {
+                match world.get(index, y) {
+                    Some(other) if *other == material => Step::Open,
+                    Some(_)
+                        if tense && world.water_neighbors(index, y, (x, y)) < TENSION_NEIGHBORS =>
+                    {
+                        Step::Open
+                    }
+                    Some(other) if slides(material, *other) => {
+                        if world.swap(x, y, index, y) {
+                            world.warm_up(x, y);
 
-        self.materials[a] = temp_b;
-        self.materials[b] = temp_a;
+                            return Step::Moved;
+                        }
 
-        if temp_b != Material::Air {
-            self.dirty[a] = true;
-        }
-        if temp_a != Material::Air {
-            self.dirty[b] = true;
+                        Step::Open
+                    }
+                    _ => Step::Blocked,
+                }
+            });
         }
+    }
 
-        let temp_a = self.tints[a];
-        let temp_b = self.tints[b];
+    /// Counts the water cells around `(x, y)`, diagonals included, leaving out the cell at `away`.
+    fn water_neighbors(&self, x: usize, y: usize, away: (usize, usize)) -> usize {
+        let mut neighbors = 0;
 
-        self.tints[a] = temp_b;
-        self.tints[b] = temp_a;
+        for j in y.saturating_sub(1)..=y + 1 {
+            for i in x.saturating_sub(1)..=x + 1 {
+                if (i, j) != (x, y) && (i, j) != away && self.get(i, j) == Some(&Material::Water) {
+                    neighbors += 1;
+                }
+            }
+        }
 
-        let temp_a = self.spreads[a];
-        let temp_b = self.spreads[b];
+        neighbors
+    }
 
-        self.spreads[a] = temp_b;
-        self.spreads[b] = temp_a;
+    /// Tries `step` on the columns up to `spread` away from `x`, nearest first, alternating sides
+    /// starting with `preference` unless `lean` is set. Returns true once a step moves the cell.
+    fn search_sideways<F>(
+        &mut self,
+        x: usize,
+        preference: isize,
+        spread: u8,
+        lean: bool,
+        mut step: F,
+    ) -> bool
+    where
+        F: FnMut(&mut World, usize) -> Step,
+    {
+        if lean {
+            for &dir in [preference, -preference].iter() {
+                for i in 1..=spread {
+                    let index = (x as isize) + (i as isize) * dir;
+
+                    if index < 0 || index >= self.size.width as isize {
+                        break;
+                    }
 
-        true
-    }
+                    match step(self, index as usize) {
+                        Step::Moved => return true,
+                        Step::Open => (),
+                        Step::Blocked => break,
+                    }
+                }
+            }
 
-    pub fn simulate(&mut self) {
-        if !self.hot {
-            return;
+            return false;
         }
 
-        for entry in self.dirty.iter_mut() {
-            *entry = false;
-        }
+        let mut dir = -preference;
+        let mut left_blocked = false;
+        let mut right_blocked = false;
 
-        for y in (0..self.size.height).rev() {
-            let preference: isize = if y % 2 == 0 { 1 } else { -1 };
+        for i in 1..=spread {
+            for _ in 0..2 {
+                dir = -dir;
 
-            for x in 0..self.size.width {
-                let x = if preference < 0 {
-                    self.size.width - 1 - x
-                } else {
-                    x
-                };
+                if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
+                    continue;
+                }
 
-                if self.dirty[y * self.size.width + x] {
+                let index = (x as isize) + (i as isize) * dir;
+
+                if index < 0 || index >= self.size.width as isize {
                     continue;
                 }
 
-                if let Some(index) = self.get_chunk_index(x, y) {
-                    if let Some(chunk) = self.active_chunks.get(index) {
-                        if !chunk {
-                            continue;
+                match step(self, index as usize) {
+                    Step::Moved => return true,
+                    Step::Open => (),
+                    Step::Blocked => {
+                        if dir < 0 {
+                            left_blocked = true;
+                        } else {
+                            right_blocked = true;
                         }
                     }
-                } else {
-                    // I do not think this will ever be reached, but you can never be too safe!
-                    continue;
                 }
+            }
 
-                let material = self.materials[y * self.size.width + x];
+            if left_blocked && right_blocked {
+                break;
+            }
+        }
 
-                (|| match material {
-                    Material::Sand => {
-                        if let Some(material) = self.get(x, y + 1) {
-                            match State::from(*material) {
-                                State::Gas | State::Liquid => {
-                                    if self.swap(x, y, x, y + 1) {
-                                        self.warm_up(x, y + 1);
+        false
+    }
 
-                                        return;
-                                    }
-                                }
-                                _ => (),
-                            }
-                        }
+    /// Holds back the active chunks past the cap until the next tick.
+    fn defer_excess_chunks(&mut self, left: usize, top: usize, right: usize, bottom: usize) {
+        let mut active: Vec<usize> = self
+            .active_chunks
+            .iter()
+            .filter(|&i| {
+                let (cx, cy) = (i % self.chunk_columns, i / self.chunk_columns);
+
+                cx >= left && cx < right && cy >= top && cy < bottom
+            })
+            .collect();
+
+        if active.len() <= self.max_active_chunks as usize {
+            for &i in active.iter() {
+                self.deferrals[i] = 0;
+            }
 
-                        let spread = self.spreads[y * self.size.width + x];
-
-                        let mut left_blocked = false;
-                        let mut right_blocked = false;
-
-                        let mut dir = -preference;
-
-                        for i in 1..(spread + 1) {
-                            for _ in 0..2 {
-                                dir = -dir;
-
-                                let swapped = (|| {
-                                    if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
-                                        return false;
-                                    }
-
-                                    let index = (x as isize) + (i as isize) * dir;
-
-                                    if index < 0 || index >= self.size.width as isize {
-                                        return false;
-                                    }
-
-                                    let index = index as usize;
-
-                                    let blocked = match self.get(index, y) {
-                                        Some(Material::Sand) => false,
-                                        Some(material)
-                                            if matches!(State::from(*material), State::Gas) =>
-                                        {
-                                            false
-                                        }
-                                        _ => true,
-                                    };
-
-                                    let mut update_blockade = || {
-                                        if dir < 0 {
-                                            left_blocked = true;
-                                        } else {
-                                            right_blocked = true;
-                                        }
-                                    };
-
-                                    if blocked {
-                                        update_blockade();
-
-                                        return false;
-                                    }
-
-                                    match self.get(index, y + 1) {
-                                        Some(Material::Sand) => false,
-                                        Some(material)
-                                            if matches!(State::from(*material), State::Gas) =>
-                                        {
-                                            if self.swap(x, y, index, y + 1) {
-                                                self.warm_up(x, y + 1);
-
-                                                return true;
-                                            }
-
-                                            false
-                                        }
-                                        _ => {
-                                            update_blockade();
-
-                                            false
-                                        }
-                                    }
-                                })();
-
-                                if swapped {
-                                    return;
-                                }
-                            }
+            return;
+        }
 
-                            if left_blocked && right_blocked {
-                                break;
-                            }
-                        }
-                    }
+        // Whatever has waited the longest goes first, so nothing is held back forever.
+        active.sort_by_key(|&i| std::cmp::Reverse(self.deferrals[i]));
 
-                    Material::Water => {
-                        if let Some(material) = self.get(x, y + 1) {
-                            if let State::Gas = State::from(*material) {
-                                if self.swap(x, y, x, y + 1) {
-                                    self.warm_up(x, y + 1);
+        let (kept, deferred) = active.split_at(self.max_active_chunks as usize);
 
-                                    return;
-                                }
-                            }
-                        }
+        for &i in kept.iter() {
+            self.deferrals[i] = 0;
+        }
 
-                        let spread = self.spreads[y * self.size.width + x];
-
-                        let mut dir = -preference;
-                        let mut left_blocked = false;
-                        let mut right_blocked = false;
-
-                        for i in 1..(spread + 1) {
-                            for _ in 0..2 {
-                                dir = -dir;
-
-                                let swapped = (|| {
-                                    if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
-                                        return false;
-                                    }
-
-                                    let index = (x as isize) + (i as isize) * dir;
-
-                                    if index < 0 || index >= self.size.width as isize {
-                                        return false;
-                                    }
-
-                                    let index = index as usize;
-
-                                    let blocked = match self.get(index, y) {
-                                        Some(Material::Water) => false,
-                                        Some(material)
-                                            if matches!(State::from(*material), State::Gas) =>
-                                        {
-                                            false
-                                        }
-                                        _ => true,
-                                    };
-
-                                    let mut update_blockade = || {
-                                        if dir < 0 {
-                                            left_blocked = true;
-                                        } else {
-                                            right_blocked = true;
-                                        }
-                                    };
-
-                                    if blocked {
-                                        update_blockade();
-
-                                        return false;
-                                    }
-
-                                    match self.get(index, y + 1) {
-                                        Some(Material::Water) => false,
-                                        Some(material)
-                                            if matches!(State::from(*material), State::Gas) =>
-                                        {
-                                            if self.swap(x, y, index, y + 1) {
-                                                self.warm_up(x, y + 1);
-
-                                                return true;
-                                            }
-
-                                            false
-                                        }
-                                        _ => {
-                                            update_blockade();
-
-                                            false
-                                        }
-                                    }
-                                })();
-
-                                if swapped {
-                                    return;
-                                }
-                            }
+        for &i in deferred.iter() {
+            self.deferrals[i] += 1;
 
-                            if left_blocked && right_blocked {
-                                break;
-                            }
-                        }
+            self.active_chunks.set(i, false);
+            self.forecast.set(i, true);
+            self.forecast_bounds[i].include(self.active_bounds[i]);
+            self.active_bounds[i] = Bounds::EMPTY;
+        }
+    }
 
-                        let mut dir = -preference;
-                        let mut left_blocked = false;
-                        let mut right_blocked = false;
-
-                        for i in 1..(spread + 1) {
-                            for _ in 0..2 {
-                                dir = -dir;
-
-                                let swapped = (|| {
-                                    if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
-                                        return false;
-                                    }
-
-                                    let index = (x as isize) + (i as isize) * dir;
-
-                                    if index < 0 || index >= self.size.width as isize {
-                                        return false;
-                                    }
-
-                                    let index = index as usize;
-
-                                    let mut update_blockade = || {
-                                        if dir < 0 {
-                                            left_blocked = true;
-                                        } else {
-                                            right_blocked = true;
-                                        }
-                                    };
-
-                                    match self.get(index, y) {
-                                        Some(Material::Water) => false,
-                                        Some(material)
-                                            if matches!(State::from(*material), State::Gas) =>
-                                        {
-                                            if self.swap(x, y, index, y) {
-                                                self.warm_up(x, y);
-
-                                                return true;
-                                            }
-
-                                            false
-                                        }
-                                        _ => {
-                                            update_blockade();
-
-                                            false
-                                        }
-                                    }
-                                })();
-
-                                if swapped {
-                                    return;
-                                }
-                            }
+    /// Wraps up the current tick, handing the chunks it covered their activity for the next one.
+    fn end_tick(&mut self) -> TickReport {
+        let Tick {
+            left,
+            top,
+            right,
+            bottom,
+            ..
+        } = match self.tick.take() {
+            Some(tick) => tick,
+            None => {
+                return TickReport {
+                    moved_cells: 0,
+                    active_chunks: 0,
+                    still_hot: self.hot,
+                }
+            }
+        };
+
+        self.front.clear();
+        self.window = Bounds::ALL;
+        self.changes_stale = true;
+        self.ticks += 1;
+
+        // Every move flags both of its cells, so whatever is left as air was only moved out of.
+        self.active_cells = self
+            .moving
+            .iter()
+            .filter(|&&index| self.materials[index as usize] != Material::Air)
+            .count() as u32;
+
+        #[cfg(feature = "debug-checks")]
+        {
+            self.last_region = (left, top, right, bottom);
+        }
 
-                            if left_blocked && right_blocked {
-                                break;
-                            }
-                        }
-                    }
+        // Nothing is dirty between ticks.
+        self.dirty.clear();
 
-                    Material::Smoke => {
-                        if let Some(Material::Air) = self.get(x, y - 1) {
-                            if self.swap(x, y, x, y - 1) {
-                                self.warm_up(x, y - 1);
-                                return;
-                            }
-                        }
+        self.hot = false;
 
-                        let spread = self.spreads[y * self.size.width + x];
+        let mut scanned = 0;
 
-                        let mut dir = -preference;
-                        let mut left_blocked = false;
-                        let mut right_blocked = false;
+        for i in 0..self.forecast.len() {
+            let (cx, cy) = (i % self.chunk_columns, i / self.chunk_columns);
 
-                        for i in 1..(spread + 1) {
-                            for _ in 0..2 {
-                                dir = -dir;
+            // Chunks outside of the region were never scanned, so anything queued for them stays
+            // queued.
+            self.activity[i] = if cx >= left && cx < right && cy >= top && cy < bottom {
+                let was_active = self.active_chunks.get(i);
 
-                                let swapped = (|| {
-                                    if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
-                                        return false;
-                                    }
+                if was_active {
+                    scanned += 1;
+                    self.touched_chunks[i] = true;
+                }
 
-                                    let index = (x as isize) + (i as isize) * dir;
+                self.active_chunks.set(i, self.forecast.get(i));
+                self.active_bounds[i] = self.forecast_bounds[i];
+                self.forecast_bounds[i] = Bounds::EMPTY;
+                self.forecast.set(i, false);
 
-                                    if index < 0 || index >= self.size.width as isize {
-                                        return false;
-                                    }
+                if was_active
+                    && !self.active_chunks.get(i)
+                    && self.on_region_edge(cx, cy, left, top, right, bottom)
+                {
+                    self.fenced.set(i, true);
+                }
 
-                                    let index = index as usize;
+                match (was_active, self.active_chunks.get(i)) {
+                    (true, true) => 1,
+                    (false, true) => 2,
+                    // A fenced chunk waits on the world outside of the region.
+                    _ if self.fenced.get(i) => 3,
+                    _ => 0,
+                }
+            } else if self.active_chunks.get(i) || self.forecast.get(i) {
+                3
+            } else {
+                0
+            };
 
-                                    let blocked = matches!(
-                                        self.get(index, y),
-                                        Some(Material::Smoke | Material::Air)
-                                    );
+            if self.activity[i] != 0 {
+                self.hot = true;
+            }
 
-                                    let mut update_blockade = || {
-                                        if dir < 0 {
-                                            left_blocked = true;
-                                        } else {
-                                            right_blocked = true;
-                                        }
-                                    };
+            self.list_chunk(i);
+        }
 
-                                    if blocked {
-                                        update_blockade();
+        // A tick only runs while the world is hot, so settling here is always a transition.
+        if !self.hot {
+            if let Some(callback) = self.idle_callback.as_ref() {
+                callback.call();
+            }
+        }
 
-                                        return false;
-                                    }
+        TickReport {
+            moved_cells: self.moved,
+            active_chunks: scanned,
+            still_hot: self.hot,
+        }
+    }
+}
 
-                                    match self.get(index, y - 1) {
-                                        Some(Material::Smoke) => false,
-                                        Some(Material::Air) => {
-                                            if self.swap(x, y, index, y - 1) {
-                                                self.warm_up(x, y - 1);
+impl World {
+    /// Returns the `(width, height)` of the world.
+    pub fn bounds(&self) -> (usize, usize) {
+        (self.size.width, self.size.height)
+    }
 
-                                                return true;
-                                            }
+    /// Maps a position in the cell buffers back to `(x, y)`, the inverse of `index`.
+    pub fn coords(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.materials.len() {
+            return None;
+        }
 
-                                            false
-                                        }
-                                        _ => {
-                                            update_blockade();
+        Some((index % self.size.width, index / self.size.width))
+    }
 
-                                            false
-                                        }
-                                    }
-                                })();
+    /// Maps a cell to the column and row of the chunk that contains it.
+    pub fn world_to_chunk(&self, x: usize, y: usize) -> (usize, usize) {
+        (x / self.chunk_size, y / self.chunk_size)
+    }
 
-                                if swapped {
-                                    return;
-                                }
-                            }
+    /// Returns the `(x, y, width, height)` rectangle of cells covered by a chunk.
+    pub fn chunk_bounds(&self, cx: usize, cy: usize) -> (usize, usize, usize, usize) {
+        let x = (cx * self.chunk_size).min(self.size.width);
+        let y = (cy * self.chunk_size).min(self.size.height);
 
-                            if left_blocked && right_blocked {
-                                break;
-                            }
-                        }
+        let width = self.chunk_size.min(self.size.width - x);
+        let height = self.chunk_size.min(self.size.height - y);
 
-                        let mut dir = -preference;
-                        let mut left_blocked = false;
-                        let mut right_blocked = false;
-
-                        for i in 1..(spread + 1) {
-                            for _ in 0..2 {
-                                dir = -dir;
-
-                                let swapped = (|| {
-                                    if (dir < 0 && left_blocked) || (dir > 0 && right_blocked) {
-                                        return false;
-                                    }
-
-                                    let index = (x as isize) + (i as isize) * dir;
-
-                                    if index < 0 || index >= self.size.width as isize {
-                                        return false;
-                                    }
-
-                                    let index = index as usize;
-
-                                    match self.get(index, y) {
-                                        Some(Material::Smoke) => false,
-                                        Some(Material::Air) => {
-                                            if self.swap(x, y, index, y) {
-                                                self.warm_up(x, y);
-
-                                                return true;
-                                            }
-
-                                            false
-                                        }
-                                        _ => {
-                                            if dir < 0 {
-                                                left_blocked = true;
-                                            } else {
-                                                right_blocked = true;
-                                            }
-
-                                            false
-                                        }
-                                    }
-                                })();
-
-                                if swapped {
-                                    return;
-                                }
-                            }
+        (x, y, width, height)
+    }
 
-                            if left_blocked && right_blocked {
-                                break;
-                            }
-                        }
-                    }
-                    _ => (),
-                })();
-            }
-        }
+    /// The buffers behind the pointer getters, as slices.
+    pub fn materials_slice(&self) -> &[Material] {
+        &self.materials
+    }
 
-        self.hot = false;
+    pub fn tints_slice(&self) -> &[Tint] {
+        &self.tints
+    }
 
-        for (i, entry) in self.forecast.iter_mut().enumerate() {
-            if *entry {
-                self.hot = true;
-            }
+    pub fn temperatures_slice(&self) -> &[i16] {
+        &self.temperatures
+    }
 
-            self.active_chunks[i] = *entry;
+    /// Same as `cells_ptr`, packing whatever changed since the last call first.
+    pub fn cells_slice(&mut self) -> &[u16] {
+        self.pack_cells();
 
-            *entry = false;
-        }
+        &self.cells
+    }
+
+    pub fn render_rgba_slice(&mut self) -> &[u8] {
+        self.render();
+
+        &self.rgba
+    }
+
+    pub fn changes_slice(&self) -> &[u32] {
+        &self.changes
+    }
+
+    pub fn counts_slice(&self) -> &[u32] {
+        &self.counts
+    }
+
+    pub fn moved_slice(&self) -> &[bool] {
+        &self.motion
+    }
+
+    pub fn chunk_activity_slice(&self) -> &[u8] {
+        &self.activity
+    }
+
+    pub fn active_chunk_list_slice(&self) -> &[u32] {
+        &self.chunk_list
+    }
+
+    pub fn chunk_stats_slice(&self) -> &[u16] {
+        &self.chunk_stats
     }
 }
@@ -0,0 +1,3333 @@
+use super::*;
+
+fn world() -> World {
+    World::create(64, 64, 16)
+}
+
+#[test]
+fn repainting_the_same_stroke_changes_nothing() {
+    let mut world = world();
+
+    let painted = world.paint(5, 5, 40, 30, 4, Material::Sand, Tint::None, 0);
+
+    assert_eq!(painted, world.preview_paint(5, 5, 40, 30, 4).len() as u32);
+    assert_eq!(
+        world.paint(5, 5, 40, 30, 4, Material::Sand, Tint::None, 0),
+        0
+    );
+    assert_eq!(world.place(5, 5, Material::Sand, Tint::None, 0), 0);
+}
+
+#[test]
+fn generated_terrain_is_reported_as_changed() {
+    let mut world = world();
+
+    world.cells_slice();
+    world.generate_terrain(7, &TerrainOptions::new(24, 8, 16));
+
+    let solid = world
+        .materials_slice()
+        .iter()
+        .filter(|&&m| m != Material::Air)
+        .count();
+
+    assert!(solid > 0);
+    assert_eq!(world.changes_slice().len(), solid);
+    assert!(!world.changed_rects().is_empty());
+
+    let packed = world.cells_slice().to_vec();
+    let index = world.changes_slice()[0] as usize;
+
+    assert_eq!(packed[index] & 0xff, world.materials_slice()[index] as u16);
+}
+
+#[test]
+fn generated_caves_are_reported_as_changed() {
+    let mut world = world();
+
+    world.generate_caves(3, 120, 4);
+
+    let solid = world
+        .materials_slice()
+        .iter()
+        .filter(|&&m| m != Material::Air)
+        .count();
+
+    assert!(solid > 0);
+    assert_eq!(world.changes_slice().len(), solid);
+}
+
+#[test]
+fn scatter_counts_only_what_the_particle_limit_allows() {
+    let mut world = world();
+
+    world.set_particle_limit(10);
+
+    assert_eq!(
+        world.scatter(0, 0, 32, 32, Material::Sand, Tint::None, 0, 128),
+        10
+    );
+    assert_eq!(world.particle_count(), 10);
+}
+
+#[test]
+fn regions_near_the_end_of_the_address_space_do_not_overflow() {
+    let mut world = world();
+
+    world.place(63, 63, Material::Sand, Tint::None, 0);
+
+    assert!(world
+        .get_region(usize::MAX, 0, 2, 2, false)
+        .unwrap()
+        .iter()
+        .all(|&m| m == 0));
+    assert!(world.get_region(0, 0, usize::MAX, 2, true).is_err());
+    assert!(world
+        .paste_region(usize::MAX, usize::MAX, 1, 1, &[1])
+        .is_ok());
+    assert!(world.paste_region(0, 0, usize::MAX, 2, &[1]).is_err());
+    assert_eq!(
+        world.stamp_mask(
+            usize::MAX,
+            0,
+            2,
+            2,
+            &[255; 4],
+            0,
+            Material::Sand,
+            Tint::None,
+            0
+        ),
+        0
+    );
+    assert_eq!(
+        world.stamp_mask(
+            62,
+            62,
+            usize::MAX,
+            2,
+            &[255; 4],
+            0,
+            Material::Water,
+            Tint::None,
+            0
+        ),
+        2
+    );
+
+    let region = world.get_region(62, 62, 2, 2, false).unwrap();
+
+    assert_eq!(
+        region,
+        vec![
+            Material::Water as u8,
+            Material::Water as u8,
+            0,
+            Material::Sand as u8
+        ]
+    );
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn profiling_keeps_the_tick_report_and_records_stats() {
+    let mut world = world();
+
+    world.place(8, 8, Material::Sand, Tint::None, 0);
+
+    let report = world.simulate();
+    let stats = world.sim_stats();
+
+    assert_eq!(report.moved_cells, 1);
+    assert_eq!(stats.moved, 1);
+    assert_eq!(stats.chunks_active, report.active_chunks as usize);
+    assert!(stats.cells_scanned > 0);
+}
+
+#[test]
+fn chunk_activity_tells_active_forecast_and_waiting_chunks_apart() {
+    let mut world = world();
+
+    world.place(8, 13, Material::Sand, Tint::None, 0);
+    world.place(56, 2, Material::Sand, Tint::None, 0);
+
+    while world.chunk_activity_slice()[8] == 0 {
+        world.simulate_region(0, 0, 16, 64);
+
+        // The sand on the right is never reached by the region.
+        assert_eq!(world.chunk_activity_slice()[3], 3);
+    }
+
+    // The sand fell into the third row of chunks without it having been scanned yet.
+    assert_eq!(world.chunk_activity_slice()[8], 2);
+
+    world.simulate_region(0, 0, 16, 64);
+
+    assert_eq!(world.chunk_activity_slice()[8], 1);
+
+    world.step(200);
+
+    assert!(world
+        .chunk_activity_slice()
+        .iter()
+        .all(|&activity| activity == 0));
+}
+
+#[test]
+fn tint_values_are_stable() {
+    assert_eq!(Tint::None as u8, 0);
+    assert_eq!(Tint::Faint as u8, 1);
+    assert_eq!(Tint::Dark as u8, 2);
+    assert_eq!(Tint::Shaded as u8, 3);
+    assert_eq!(Tint::Darker as u8, 4);
+    assert_eq!(Tint::Deep as u8, 5);
+    assert_eq!(Tint::Darkest as u8, 6);
+    assert_eq!(Tint::Deepest as u8, 7);
+
+    for value in 0..TINT_LEVELS {
+        assert!(Tint::try_from(value).map(|tint| tint as u8) == Ok(value));
+    }
+
+    assert!(Tint::try_from(TINT_LEVELS).is_err());
+}
+
+#[test]
+fn legacy_tints_keep_their_shade() {
+    assert!(tint_from_legacy(0) == Ok(Tint::None));
+    assert!(tint_from_legacy(1) == Ok(Tint::Dark));
+    assert!(tint_from_legacy(2) == Ok(Tint::Darker));
+    assert!(tint_from_legacy(3) == Ok(Tint::Darkest));
+    assert!(tint_from_legacy(4).is_err());
+}
+
+#[test]
+fn every_tint_mode_places_its_tint() {
+    let modes = [
+        (TintMode::None, Tint::None),
+        (TintMode::Faint, Tint::Faint),
+        (TintMode::Dark, Tint::Dark),
+        (TintMode::Shaded, Tint::Shaded),
+        (TintMode::Darker, Tint::Darker),
+        (TintMode::Deep, Tint::Deep),
+        (TintMode::Darkest, Tint::Darkest),
+        (TintMode::Deepest, Tint::Deepest),
+    ];
+
+    for (mode, tint) in modes {
+        let mut world = world();
+
+        assert!(TintMode::try_from(mode as u8).is_ok_and(|parsed| parsed as u8 == mode as u8));
+
+        world.set_default_tint_mode(Material::Rock, mode);
+        world.place_default(4, 4, Material::Rock);
+
+        assert!(world.tints_slice()[4 * 64 + 4] == tint);
+    }
+}
+
+#[test]
+fn render_rgba_draws_all_eight_tints() {
+    let mut world = world();
+
+    for (x, &tint) in Tint::LEVELS.iter().enumerate() {
+        world.place(x, 0, Material::Rock, tint, 0);
+    }
+
+    world.set_cell_color_override(0, 1, 0x1234_5678);
+
+    let pixels = world.render_rgba_slice();
+    let pixel = |index: usize| &pixels[index * 4..index * 4 + 4];
+
+    assert_eq!(pixels.len(), 64 * 64 * 4);
+    assert_eq!(pixel(0), &material_color(Material::Rock).to_be_bytes());
+    assert_eq!(pixel(64), &[0x12, 0x34, 0x56, 0x78]);
+
+    // Each tint is drawn strictly darker than the one before it, at full alpha.
+    for x in 1..Tint::LEVELS.len() {
+        assert!(pixel(x)[..3].iter().zip(pixel(x - 1)).all(|(a, b)| a < b));
+        assert_eq!(pixel(x)[3], 0xff);
+    }
+}
+
+#[test]
+fn tint_levels_darken_evenly() {
+    for (level, &tint) in Tint::LEVELS.iter().enumerate() {
+        assert_eq!(tint_level(tint) as usize, level);
+    }
+
+    assert!(tint_level(Tint::Dark) < tint_level(Tint::Darker));
+    assert!(tint_level(Tint::Darker) < tint_level(Tint::Darkest));
+}
+
+#[test]
+fn recordings_have_a_single_version() {
+    let mut world = world();
+
+    world.start_recording();
+    world.place(4, 4, Material::Sand, Tint::Dark, 0);
+    world.simulate();
+
+    let mut log = world.stop_recording();
+
+    assert_eq!(log[4], 2);
+    assert!(World::replay(&log).is_ok());
+
+    // Logs from before tints were reordered store them under different values.
+    log[4] = 1;
+
+    assert!(World::replay(&log).is_err());
+}
+
+/// Pours water down a shaft cut through rock, over and over, so erosion has plenty of chances
+/// to wear the walls down.
+fn pour_down_shaft(world: &mut World, times: usize) {
+    for _ in 0..times {
+        world.reset_region(30, 0, 3, 64);
+        world.paint(31, 0, 31, 7, 1, Material::Water, Tint::None, 5);
+        world.step(60);
+    }
+}
+
+fn rock_count(world: &World) -> usize {
+    world
+        .materials_slice()
+        .iter()
+        .filter(|&&material| material == Material::Rock)
+        .count()
+}
+
+#[test]
+fn erosion_survives_a_replay() {
+    let mut world = world();
+
+    world.fill_rect(0, 8, 64, 56, Material::Rock, Tint::None, 0);
+    world.set_erosion(true);
+    world.start_recording();
+
+    pour_down_shaft(&mut world, 20);
+    world.set_erosion(false);
+    pour_down_shaft(&mut world, 5);
+    world.set_erosion(true);
+    pour_down_shaft(&mut world, 20);
+
+    let log = world.stop_recording();
+    let replayed = World::replay(&log).unwrap();
+
+    assert!(rock_count(&world) < 64 * 56 - 3 * 56);
+    assert_eq!(replayed.state_hash(), world.state_hash());
+}
+
+#[test]
+fn every_edit_and_setting_survives_a_replay() {
+    let mut world = world();
+
+    world.enable_history(8);
+    world.enable_rewind(4, 2);
+    world.fill_rect(0, 48, 64, 16, Material::Rock, Tint::None, 0);
+    world.start_recording();
+
+    world.set_flow_bias(-20);
+    world.set_max_fall_speed(3);
+    world.set_default_spread(Material::Water, 6);
+    world.set_default_tint_mode(Material::Sand, TintMode::Random);
+    world.fill_rect(4, 4, 8, 8, Material::Sand, Tint::Dark, 0);
+    world.fill_rect_pattern(
+        20,
+        4,
+        8,
+        8,
+        &PatternFill::checker(2),
+        Material::Water,
+        Tint::None,
+        4,
+    );
+    world.place_polygon(&[40, 4, 56, 4, 48, 16], Material::Oil, Tint::None, 2);
+    world.stamp_mask(
+        4,
+        20,
+        2,
+        2,
+        &[255, 0, 0, 255],
+        128,
+        Material::Sand,
+        Tint::None,
+        0,
+    );
+    world.scatter(0, 0, 64, 20, Material::Sand, Tint::Darker, 0, 40);
+    world
+        .paint_mixed(10, 30, 50, 30, 2, &[2, 3], &[3, 1], Tint::None, 3)
+        .unwrap();
+    world.paint_tapered(10, 40, 1, 50, 40, 3, Material::Water, Tint::None, 5);
+    world.place_natural(32, 2, Material::Sand);
+    world.paint_default(0, 0, 63, 0, 1, Material::Water);
+
+    let region = world.get_region(0, 0, 8, 8, true).unwrap();
+
+    world.paste_region(56, 0, 8, 8, &region).unwrap();
+    world.undo();
+    world.redo();
+    world.simulate_region(0, 0, 32, 32);
+
+    for _ in 0..40 {
+        world.simulate_budgeted(0);
+    }
+
+    world.debug_step();
+    world.set_substeps(2);
+    world.step(10);
+    world.rewind(2);
+    world.invert_region(0, 40, 64, 8, Material::Sand);
+    world.step(10);
+
+    let log = world.stop_recording();
+    let replayed = World::replay(&log).unwrap();
+
+    assert_eq!(replayed.state_hash(), world.state_hash());
+    assert_eq!(replayed.ticks, world.ticks);
+}
+
+#[test]
+fn presets_and_generators_survive_a_replay() {
+    let mut world = world();
+
+    world.start_recording();
+
+    world.load_preset(Preset::Volcano);
+    world.step(20);
+    world.generate_terrain(7, &TerrainOptions::new(24, 8, 16));
+    world.step(20);
+    world.generate_caves(11, 120, 3);
+    world.step(20);
+
+    let log = world.stop_recording();
+    let replayed = World::replay(&log).unwrap();
+
+    assert_eq!(replayed.state_hash(), world.state_hash());
+}
+
+#[test]
+#[allow(deprecated)]
+fn enabling_a_material_is_the_same_as_unpausing_it() {
+    let mut paused = world();
+    let mut disabled = world();
+
+    for world in [&mut paused, &mut disabled].iter_mut() {
+        world.fill_rect(8, 0, 16, 16, Material::Sand, Tint::None, 0);
+        world.fill_rect(40, 0, 16, 16, Material::Water, Tint::None, 4);
+    }
+
+    paused.set_material_paused(Material::Sand, true);
+    disabled.set_material_enabled(Material::Sand, false);
+    paused.step(20);
+    disabled.step(20);
+
+    assert_eq!(paused.state_hash(), disabled.state_hash());
+    assert!(paused.get(8, 0) == Some(&Material::Sand));
+
+    paused.set_material_paused(Material::Sand, false);
+    disabled.set_material_enabled(Material::Sand, true);
+    paused.step(20);
+    disabled.step(20);
+
+    assert_eq!(paused.state_hash(), disabled.state_hash());
+    assert!(paused.get(8, 0) == Some(&Material::Air));
+}
+
+#[test]
+fn liquids_stratify_by_density() {
+    // Acid neutralizes on contact with water, so each pairing settles in a world of its own.
+    for &heavy in [Material::Water, Material::Acid].iter() {
+        let mut world = World::create(16, 16, 8);
+
+        world.fill_rect(0, 8, 16, 4, heavy, Tint::None, 3);
+        world.fill_rect(0, 12, 16, 4, Material::Oil, Tint::None, 4);
+        world.step(300);
+
+        let rows = world.materials_slice().chunks(16).collect::<Vec<_>>();
+
+        assert!(rows[8..12]
+            .iter()
+            .all(|row| row.iter().all(|&m| m == Material::Oil)));
+        assert!(rows[12..].iter().all(|row| row.iter().all(|&m| m == heavy)));
+    }
+}
+
+#[test]
+fn adjacent_pattern_fills_tile_seamlessly() {
+    let mut world = world();
+    let checker = PatternFill::checker(2);
+
+    assert_eq!(
+        world.fill_rect_pattern(0, 0, 5, 8, &checker, Material::Rock, Tint::None, 0),
+        20
+    );
+    world.fill_rect_pattern(5, 0, 3, 8, &checker, Material::Rock, Tint::None, 0);
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let rock = (x / 2 + y / 2) % 2 == 0;
+
+            assert_eq!(world.get(x, y) == Some(&Material::Rock), rock);
+        }
+    }
+
+    world.fill_rect_pattern(
+        0,
+        10,
+        8,
+        4,
+        &PatternFill::stripes(1, true),
+        Material::Rock,
+        Tint::None,
+        0,
+    );
+
+    assert!(world.get(3, 10) == Some(&Material::Rock));
+    assert!(world.get(3, 11) == Some(&Material::Air));
+}
+
+#[test]
+fn chunks_along_the_far_edges_are_clipped() {
+    let world = World::create(100, 70, 32);
+
+    assert_eq!((world.chunk_columns(), world.chunk_rows()), (4, 3));
+    assert_eq!(world.world_to_chunk(0, 0), (0, 0));
+    assert_eq!(world.world_to_chunk(31, 32), (0, 1));
+    assert_eq!(world.world_to_chunk(99, 69), (3, 2));
+    assert_eq!(world.chunk_bounds(1, 1), (32, 32, 32, 32));
+    assert_eq!(world.chunk_bounds(3, 0), (96, 0, 4, 32));
+    assert_eq!(world.chunk_bounds(0, 2), (0, 64, 32, 6));
+    assert_eq!(world.chunk_bounds(3, 2), (96, 64, 4, 6));
+}
+
+#[test]
+fn previewing_a_stroke_leaves_the_world_alone() {
+    let mut world = world();
+    let before = world.state_hash();
+    let preview = world.preview_paint(60, 2, 70, 20, 3);
+
+    assert_eq!(world.state_hash(), before);
+    assert!(preview.windows(2).all(|pair| pair[0] < pair[1]));
+
+    world.paint(60, 2, 70, 20, 3, Material::Rock, Tint::None, 0);
+
+    let painted: Vec<u32> = (0..world.cell_count() as u32)
+        .filter(|&i| world.materials_slice()[i as usize] == Material::Rock)
+        .collect();
+
+    assert_eq!(preview, painted);
+}
+
+#[test]
+fn cells_in_partial_edge_chunks_are_simulated() {
+    let mut world = World::create(100, 100, 32);
+
+    world.place(99, 90, Material::Sand, Tint::None, 0);
+    world.place(97, 99, Material::Water, Tint::None, 5);
+    world.fill_rect(90, 98, 6, 1, Material::Rock, Tint::None, 0);
+    world.step(20);
+
+    assert!(world.get(99, 99) == Some(&Material::Sand));
+    assert!(world.get(97, 99) == Some(&Material::Air));
+    assert_eq!(world.counts_slice()[Material::Water as usize], 1);
+}
+
+#[test]
+fn retinting_and_respreading_keep_the_material() {
+    let mut world = world();
+
+    world.fill_rect(30, 56, 1, 8, Material::Water, Tint::None, 0);
+    world.step(20);
+
+    assert!(world.get(30, 56) == Some(&Material::Water));
+
+    world.set_tint(30, 63, Tint::Darker);
+
+    assert!(world.tints_slice()[63 * 64 + 30] == Tint::Darker);
+    assert!(world.get(30, 63) == Some(&Material::Water));
+
+    world.set_spread_rect(30, 56, 1, 8, 5);
+    world.step(40);
+
+    assert!(world.get(30, 56) == Some(&Material::Air));
+    assert_eq!(world.counts_slice()[Material::Water as usize], 8);
+}
+
+#[test]
+fn hot_puddles_dry_out_from_the_top() {
+    let mut world = world();
+
+    world.fill_rect(0, 60, 64, 4, Material::Water, Tint::None, 5);
+    world.set_ambient_temperature(40);
+    world.step(600);
+
+    assert_eq!(world.counts_slice()[Material::Water as usize], 256);
+
+    world.set_evaporation(true);
+    world.step(600);
+
+    let water = world.counts_slice()[Material::Water as usize];
+
+    assert!(water < 256);
+    assert!(world.materials_slice()[63 * 64..]
+        .iter()
+        .all(|&m| m == Material::Water));
+    assert!(world.counts_slice()[Material::Steam as usize] > 0);
+}
+
+#[test]
+fn mixed_strokes_follow_their_weights() {
+    let mut world = world();
+    let (sand, rock, water) = (
+        Material::Sand as u8,
+        Material::Rock as u8,
+        Material::Water as u8,
+    );
+
+    assert!(world
+        .paint_mixed(0, 0, 63, 0, 2, &[sand], &[1, 1], Tint::None, 0)
+        .is_err());
+    assert!(world
+        .paint_mixed(0, 0, 63, 0, 2, &[sand, rock], &[0, 0], Tint::None, 0)
+        .is_err());
+    assert!(world
+        .paint_mixed(0, 0, 63, 0, 2, &[sand, 200], &[1, 1], Tint::None, 0)
+        .is_err());
+    assert_eq!(world.particle_count(), 0);
+
+    let painted = world
+        .paint_mixed(
+            0,
+            0,
+            63,
+            63,
+            63,
+            &[rock, water, sand],
+            &[3, 1, 0],
+            Tint::None,
+            0,
+        )
+        .unwrap();
+    let counts = world.counts_slice();
+
+    assert_eq!(painted, 64 * 64);
+    assert_eq!(counts[Material::Sand as usize], 0);
+    assert!(counts[Material::Rock as usize] > 2 * counts[Material::Water as usize]);
+    assert!(counts[Material::Water as usize] > 0);
+}
+
+#[test]
+fn stamping_a_mask_sets_only_opaque_cells() {
+    let mut world = world();
+    #[rustfmt::skip]
+    let alpha = [
+        255, 0, 255,
+        0, 129, 0,
+        128, 0, 255,
+    ];
+
+    assert_eq!(
+        world.stamp_mask(10, 10, 3, 3, &alpha, 128, Material::Rock, Tint::None, 0),
+        4
+    );
+
+    for j in 0..3 {
+        for i in 0..3 {
+            let opaque = alpha[j * 3 + i] > 128;
+
+            assert_eq!(world.get(10 + i, 10 + j) == Some(&Material::Rock), opaque);
+        }
+    }
+
+    assert_eq!(world.particle_count(), 4);
+}
+
+#[test]
+fn scatter_hits_its_coverage_and_skips_filled_cells() {
+    let mut world = world();
+
+    world.fill_rect(0, 0, 10, 10, Material::Rock, Tint::None, 0);
+
+    assert_eq!(
+        world.scatter(0, 0, 20, 10, Material::Sand, Tint::None, 0, 51),
+        40
+    );
+    assert_eq!(world.counts_slice()[Material::Rock as usize], 100);
+    assert_eq!(world.counts_slice()[Material::Sand as usize], 40);
+
+    // Asking for more than is left fills whatever air remains.
+    assert_eq!(
+        world.scatter(0, 0, 20, 10, Material::Sand, Tint::None, 0, 255),
+        60
+    );
+
+    let seeded = |seed| {
+        let mut world = World::create(32, 32, 8);
+
+        world.set_seed(seed);
+        world.scatter(0, 0, 32, 32, Material::Sand, Tint::None, 0, 64);
+        world.state_hash()
+    };
+
+    assert_eq!(seeded(5), seeded(5));
+    assert!(seeded(5) != seeded(6));
+}
+
+#[test]
+fn both_update_modes_settle_a_symmetric_pile_evenly() {
+    for &mode in [UpdateMode::InPlace, UpdateMode::DoubleBuffered].iter() {
+        let mut world = World::create(33, 24, 8);
+
+        world.set_update_mode(mode);
+        world.fill_rect(14, 0, 5, 10, Material::Sand, Tint::None, 1);
+        world.step(100);
+
+        let sand = |x: usize| {
+            (0..24)
+                .filter(|&y| world.get(x, y) == Some(&Material::Sand))
+                .count()
+        };
+        let left: usize = (0..16).map(sand).sum();
+        let right: usize = (17..33).map(sand).sum();
+
+        assert!(!world.is_active());
+        assert_eq!(left + sand(16) + right, 50);
+        assert!(left.max(right) - left.min(right) <= 2);
+        assert!((0..16).all(|x| (sand(x) as isize - sand(32 - x) as isize).abs() <= 1));
+    }
+}
+
+#[test]
+fn terrain_depends_only_on_its_seed() {
+    let options = TerrainOptions::new(24, 8, 16);
+    let terrain = |seed| {
+        let mut world = world();
+
+        world.place(0, 0, Material::Fire, Tint::None, 0);
+        world.generate_terrain(seed, &options);
+        world.state_hash()
+    };
+
+    assert_eq!(terrain(3), terrain(3));
+    assert!(terrain(3) != terrain(4));
+
+    let mut world = world();
+
+    world.generate_terrain(3, &options);
+
+    for (i, &material) in world.materials_slice().iter().enumerate() {
+        if material == Material::Water {
+            assert!(i / 64 >= 64 - 16);
+        }
+    }
+
+    // The ground never dips low enough for sand to reach the bottom row.
+    assert!(world.materials_slice()[63 * 64..]
+        .iter()
+        .all(|&m| m == Material::Rock));
+}
+
+#[test]
+fn fire_travels_along_a_fuse_at_a_steady_rate() {
+    let mut world = world();
+
+    world.fill_rect(0, 33, 64, 1, Material::Rock, Tint::None, 0);
+    world.fill_rect(10, 32, 40, 1, Material::Fuse, Tint::None, 0);
+    world.place(9, 32, Material::Fire, Tint::None, 0);
+
+    let mut lit = vec![None; 40];
+
+    for tick in 0..400 {
+        world.simulate();
+
+        for (i, lit) in lit.iter_mut().enumerate() {
+            if lit.is_none() && world.get(10 + i, 32) != Some(&Material::Fuse) {
+                *lit = Some(tick);
+            }
+        }
+    }
+
+    let lit: Vec<u32> = lit.into_iter().map(Option::unwrap).collect();
+    let pace = lit[1] - lit[0];
+
+    assert!(pace > 1);
+    assert!(lit.windows(2).all(|pair| pair[1] - pair[0] == pace));
+}
+
+#[test]
+fn caves_follow_their_seed_and_fill_probability() {
+    let caves = |seed, fill, passes| {
+        let mut world = world();
+
+        world.generate_caves(seed, fill, passes);
+        world
+    };
+
+    assert_eq!(caves(1, 120, 4).state_hash(), caves(1, 120, 4).state_hash());
+    assert!(caves(1, 120, 4).state_hash() != caves(2, 120, 4).state_hash());
+    assert_eq!(caves(1, 255, 0).particle_count(), 64 * 64);
+
+    // Smoothing treats the edge of the world as rock, so the corners close up.
+    assert!(caves(1, 0, 1).get(0, 0) == Some(&Material::Rock));
+
+    // With no rock at all, the whole bottom quarter floods.
+    let flooded = caves(1, 0, 0);
+
+    assert_eq!(flooded.particle_count(), 64 * 16);
+    assert_eq!(flooded.counts_slice()[Material::Water as usize], 64 * 16);
+    assert!(flooded.get(0, 47) == Some(&Material::Air));
+}
+
+#[test]
+fn generated_caves_only_wake_their_pools() {
+    let mut world = world();
+
+    world.generate_caves(5, 255, 0);
+
+    // Solid rock, with nowhere for water to go.
+    assert!(!world.active_chunks.any() && !world.forecast.any());
+
+    world.generate_caves(1, 0, 0);
+
+    let awake: Vec<bool> = (0..16)
+        .map(|i| world.active_chunks.get(i) || world.forecast.get(i))
+        .collect();
+
+    // The pool fills the bottom row of chunks, which wakes the row above it too.
+    assert!(awake[..4].iter().all(|&awake| !awake));
+    assert!(awake[8..].iter().all(|&awake| awake));
+}
+
+#[test]
+fn resizing_recounts_rows_at_the_new_width() {
+    let mut world = World::create(16, 16, 8);
+
+    world.fill_rect(0, 8, 16, 8, Material::Sand, Tint::None, 1);
+    world.resize(40, 24, Anchor::BottomLeft);
+
+    assert_eq!(world.particle_count(), 128);
+
+    // Rows holding nothing but air are skipped, so a miscounted row would leave this hanging.
+    world.place(30, 0, Material::Sand, Tint::None, 1);
+    world.step(40);
+
+    assert!(world.get(30, 23) == Some(&Material::Sand));
+
+    world.resize(8, 8, Anchor::TopLeft);
+
+    assert_eq!(world.particle_count(), 0);
+}
+
+#[test]
+fn growing_from_the_bottom_keeps_piles_on_the_floor() {
+    let mut world = World::create(32, 32, 8);
+
+    world.fill_rect(4, 24, 8, 8, Material::Sand, Tint::None, 1);
+    world.step(40);
+
+    let before = world.get_region(0, 0, 32, 32, false).unwrap();
+
+    world.resize(48, 64, Anchor::BottomLeft);
+
+    assert_eq!(world.bounds(), (48, 64));
+    assert_eq!(world.get_region(0, 32, 32, 32, false).unwrap(), before);
+    assert!(world.get(5, 63) == Some(&Material::Sand));
+
+    world.step(40);
+
+    assert_eq!(world.get_region(0, 32, 32, 32, false).unwrap(), before);
+
+    world.resize(32, 32, Anchor::TopLeft);
+
+    assert_eq!(world.particle_count(), 0);
+}
+
+#[test]
+fn presets_load_reproducibly_and_undo_in_one_step() {
+    let presets = [
+        Preset::Hourglass,
+        Preset::Waterfall,
+        Preset::Volcano,
+        Preset::Aquarium,
+    ];
+    let mut hashes = Vec::new();
+
+    for &preset in presets.iter() {
+        let mut world = world();
+
+        world.enable_history(4);
+        world.place(0, 0, Material::Rock, Tint::None, 0);
+
+        let before = world.state_hash();
+
+        world.load_preset(preset);
+
+        assert!(world.particle_count() > 0);
+
+        let loaded = world.state_hash();
+
+        hashes.push(loaded);
+        world.load_preset(preset);
+
+        assert_eq!(world.state_hash(), loaded);
+
+        assert!(world.undo());
+        assert!(world.undo());
+        assert_eq!(world.state_hash(), before);
+    }
+
+    hashes.sort_unstable();
+    hashes.dedup();
+
+    assert_eq!(hashes.len(), presets.len());
+}
+
+#[test]
+fn regions_copy_between_worlds() {
+    let mut source = world();
+    let mut target = World::create(32, 32, 8);
+
+    source.set_seed(9);
+    source.scatter(0, 0, 64, 64, Material::Sand, Tint::Darker, 1, 100);
+    source.fill_rect(10, 10, 4, 4, Material::Water, Tint::Faint, 5);
+
+    let region = source.get_region(8, 8, 20, 12, true).unwrap();
+    let solid = source
+        .get_region(8, 8, 20, 12, false)
+        .unwrap()
+        .iter()
+        .filter(|&&m| m != 0)
+        .count();
+
+    assert_eq!(region.len(), 20 * 12 * 3);
+    assert_eq!(
+        target.paste_region(4, 4, 20, 12, &region).unwrap(),
+        solid as u32
+    );
+    assert_eq!(target.get_region(4, 4, 20, 12, true).unwrap(), region);
+    assert!(target
+        .get_region(0, 0, 4, 4, false)
+        .unwrap()
+        .iter()
+        .all(|&m| m == 0));
+}
+
+#[test]
+fn default_placement_uses_each_materials_defaults() {
+    let mut world = world();
+
+    world.place_default(0, 0, Material::Water);
+    world.set_default_spread(Material::Sand, 3);
+    world.set_default_tint_mode(Material::Sand, TintMode::Darker);
+    world.place_default(1, 0, Material::Sand);
+    world.paint_default(4, 0, 7, 0, 0, Material::Sand);
+
+    assert_eq!(
+        world.get_region(0, 0, 2, 1, true).unwrap(),
+        vec![
+            Material::Water as u8,
+            Tint::None as u8,
+            5,
+            Material::Sand as u8,
+            Tint::Darker as u8,
+            3
+        ]
+    );
+    assert_eq!(
+        world.get_region(4, 0, 3, 1, true).unwrap(),
+        [Material::Sand as u8, Tint::Darker as u8, 3].repeat(3)
+    );
+}
+
+/// Finds the first cell holding `material`, scanning row by row.
+fn find(world: &World, material: Material) -> Option<(usize, usize)> {
+    let index = world
+        .materials_slice()
+        .iter()
+        .position(|&m| m == material)?;
+
+    world.coords(index)
+}
+
+#[test]
+fn falling_sand_picks_up_speed() {
+    let mut world = World::create(8, 128, 16);
+
+    world.set_max_fall_speed(6);
+    world.place(4, 0, Material::Sand, Tint::None, 0);
+
+    let mut heights = vec![0];
+
+    for _ in 0..8 {
+        world.simulate();
+        heights.push(find(&world, Material::Sand).unwrap().1);
+    }
+
+    let falls: Vec<usize> = heights.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+    assert_eq!(falls, vec![1, 2, 3, 4, 5, 6, 6, 6]);
+}
+
+#[test]
+fn tapered_strokes_narrow_toward_the_smaller_end() {
+    let mut even = world();
+    let mut painted = world();
+
+    even.paint(10, 32, 50, 32, 3, Material::Sand, Tint::None, 0);
+    painted.paint_tapered(10, 32, 3, 50, 32, 3, Material::Sand, Tint::None, 0);
+
+    assert_eq!(painted.state_hash(), even.state_hash());
+
+    let mut tapered = world();
+
+    tapered.paint_tapered(10, 32, 6, 50, 32, 1, Material::Rock, Tint::None, 0);
+
+    let thickness = |x: usize| {
+        (0..64)
+            .filter(|&y| tapered.get(x, y) == Some(&Material::Rock))
+            .count()
+    };
+
+    assert_eq!(thickness(10), 13);
+    assert_eq!(thickness(50), 3);
+    assert!((10..50).all(|x| thickness(x) >= thickness(x + 1)));
+    assert!(tapered.get(4, 32) == Some(&Material::Rock));
+    assert!(tapered.get(52, 32) == Some(&Material::Air));
+}
+
+#[test]
+fn rectangles_report_the_material_they_contain() {
+    let mut world = world();
+
+    world.place(62, 62, Material::Water, Tint::None, 0);
+
+    assert!(world.contains_material_in_rect(60, 60, 3, 3, Material::Water));
+    assert!(!world.contains_material_in_rect(60, 60, 2, 2, Material::Water));
+    assert!(!world.contains_material_in_rect(60, 60, 3, 3, Material::Sand));
+    assert!(world.contains_material_in_rect(50, 50, 100, 100, Material::Water));
+    assert!(!world.contains_material_in_rect(64, 0, 10, 10, Material::Air));
+    assert!(!world.contains_material_in_rect(0, 0, 0, 10, Material::Air));
+    assert!(!world.contains_material_in_rect(usize::MAX, 0, 2, 2, Material::Air));
+}
+
+#[test]
+fn placements_count_only_cells_whose_material_changed() {
+    let mut world = World::create(16, 16, 8);
+
+    assert_eq!(
+        world.fill_rect(0, 0, 4, 4, Material::Rock, Tint::None, 0),
+        16
+    );
+    assert_eq!(
+        world.fill_rect(2, 0, 4, 4, Material::Rock, Tint::Dark, 0),
+        8
+    );
+    assert_eq!(world.place(0, 0, Material::Rock, Tint::Darkest, 0), 0);
+    assert_eq!(world.place(0, 0, Material::Sand, Tint::None, 0), 1);
+    assert_eq!(world.place(99, 0, Material::Sand, Tint::None, 0), 0);
+    assert_eq!(world.fill_air(Material::Water, Tint::None, 0), 256 - 24);
+    assert_eq!(world.fill_air(Material::Water, Tint::None, 0), 0);
+    assert_eq!(
+        world.place_polygon(&[0, 0, 8, 0, 8, 8, 0, 8], Material::Water, Tint::None, 0),
+        24
+    );
+}
+
+#[test]
+fn viruses_spread_at_most_one_cell_per_tick() {
+    let run = |seed| {
+        let mut world = world();
+
+        world.set_seed(seed);
+        world.fill_rect(0, 0, 64, 64, Material::Water, Tint::None, 0);
+        world.place(32, 32, Material::Virus, Tint::None, 0);
+
+        for tick in 1..=40 {
+            world.simulate();
+
+            for (i, &material) in world.materials_slice().iter().enumerate() {
+                if material == Material::Virus {
+                    let (x, y) = world.coords(i).unwrap();
+                    let distance = (x as isize - 32).abs() + (y as isize - 32).abs();
+
+                    assert!(distance <= tick);
+                }
+            }
+        }
+
+        world
+    };
+
+    let world = run(2);
+
+    assert!(world.counts_slice()[Material::Virus as usize] > 1);
+    assert_eq!(world.state_hash(), run(2).state_hash());
+}
+
+#[test]
+fn stepping_matches_simulating_and_stops_once_settled() {
+    let mut stepped = world();
+    let mut simulated = world();
+
+    for world in [&mut stepped, &mut simulated].iter_mut() {
+        world.fill_rect(20, 0, 8, 8, Material::Sand, Tint::None, 1);
+    }
+
+    assert_eq!(stepped.step(10), 10);
+
+    for _ in 0..10 {
+        simulated.simulate();
+    }
+
+    assert_eq!(stepped.state_hash(), simulated.state_hash());
+
+    let ran = stepped.step(1000);
+
+    assert!(ran > 0 && ran < 1000);
+    assert!(!stepped.is_active());
+    assert_eq!(stepped.tick(), 10 + ran as u64);
+    assert_eq!(stepped.step(5), 0);
+}
+
+#[test]
+fn dirty_rects_match_full_chunk_scans() {
+    let run = |dirty_rects| {
+        let mut world = world();
+
+        world.set_dirty_rects(dirty_rects);
+        world.fill_rect(0, 48, 64, 2, Material::Rock, Tint::None, 0);
+        world.fill_rect(5, 0, 6, 10, Material::Sand, Tint::None, 1);
+        world.fill_rect(40, 20, 10, 10, Material::Water, Tint::None, 5);
+        world.place(60, 60, Material::Smoke, Tint::None, 3);
+
+        let mut hashes = Vec::new();
+
+        for tick in 0..200 {
+            if tick == 50 {
+                world.paint(0, 30, 63, 30, 1, Material::Oil, Tint::None, 4);
+            }
+
+            world.simulate();
+            hashes.push(world.state_hash());
+        }
+
+        hashes
+    };
+
+    assert_eq!(run(true), run(false));
+}
+
+#[test]
+fn simulating_a_region_leaves_the_rest_waiting() {
+    let mut world = world();
+
+    world.place(8, 0, Material::Sand, Tint::None, 0);
+    world.place(40, 0, Material::Sand, Tint::None, 0);
+
+    for _ in 0..10 {
+        world.simulate_region(0, 0, 32, 32);
+    }
+
+    assert!(world.get(8, 10) == Some(&Material::Sand));
+    assert!(world.get(40, 0) == Some(&Material::Sand));
+
+    for _ in 0..100 {
+        world.simulate_region(0, 0, 32, 32);
+    }
+
+    // The bottom of the region behaves like the floor.
+    assert!(world.get(8, 31) == Some(&Material::Sand));
+
+    world.step(100);
+
+    assert!(world.get(8, 63) == Some(&Material::Sand));
+    assert!(world.get(40, 63) == Some(&Material::Sand));
+}
+
+#[test]
+fn sampling_a_line_reads_each_cell_in_order() {
+    let mut world = World::create(8, 8, 4);
+
+    world.place(1, 1, Material::Rock, Tint::None, 0);
+    world.place(2, 2, Material::Sand, Tint::None, 0);
+    world.place(3, 3, Material::Water, Tint::None, 0);
+    world.place(3, 0, Material::Oil, Tint::None, 0);
+
+    let (air, rock, sand, water, oil) = (0, 1, 2, 3, 5);
+
+    assert_eq!(
+        world.material_at_cursor_line(0, 0, 4, 4),
+        vec![air, rock, sand, water, air]
+    );
+    assert_eq!(
+        world.material_at_cursor_line(4, 4, 0, 0),
+        vec![air, water, sand, rock, air]
+    );
+    assert_eq!(
+        world.material_at_cursor_line(0, 0, 3, 0),
+        vec![air, air, air, oil]
+    );
+    assert_eq!(
+        world.material_at_cursor_line(3, 3, 10, 3),
+        vec![water, air, air, air, air]
+    );
+}
+
+#[test]
+fn settled_worlds_stay_inactive_until_the_next_edit() {
+    let mut world = world();
+
+    assert!(!world.is_active());
+
+    world.paint(20, 0, 40, 0, 3, Material::Sand, Tint::None, 1);
+
+    assert!(world.is_active());
+
+    world.step(500);
+
+    assert!(!world.is_active());
+
+    let settled = world.state_hash();
+
+    for _ in 0..10 {
+        assert!(!world.simulate().still_hot);
+    }
+
+    assert!(!world.is_active());
+    assert_eq!(world.state_hash(), settled);
+
+    world.place(0, 0, Material::Sand, Tint::None, 1);
+
+    assert!(world.is_active());
+}
+
+#[test]
+fn the_chunk_grid_and_its_activity_are_exposed() {
+    let mut world = World::create(70, 40, 16);
+
+    assert_eq!((world.chunk_columns(), world.chunk_rows()), (5, 3));
+    assert_eq!(world.chunk_size(), 16);
+    assert_eq!(world.active_chunk_count(), 0);
+
+    // A change wakes up its chunk along with the chunks around it.
+    world.place(8, 8, Material::Sand, Tint::None, 0);
+
+    assert_eq!(world.active_chunk_count(), 4);
+
+    world.place(40, 20, Material::Sand, Tint::None, 0);
+
+    assert_eq!(world.active_chunk_count(), 4 + 9 - 2);
+
+    world.step(100);
+
+    assert_eq!(world.active_chunk_count(), 0);
+}
+
+#[test]
+fn strokes_are_painted_around_the_centers_of_walk_line() {
+    let size = Size {
+        width: 16,
+        height: 16,
+    };
+    let centers = |x1, y1, x2, y2| {
+        let mut centers = Vec::new();
+
+        walk_line(size, 0, x1, y1, x2, y2, |x, y| centers.push((x, y)));
+        centers
+    };
+
+    assert_eq!(centers(5, 2, 5, 6), vec![(5, 2), (5, 3), (5, 4), (5, 5)]);
+    assert_eq!(centers(2, 5, 4, 5), vec![(2, 5), (2, 5), (3, 5), (3, 5)]);
+    assert_eq!(centers(0, 0, 2, 2), vec![(0, 0), (0, 1), (1, 1), (1, 2)]);
+
+    for &(x1, y1, x2, y2) in [(5, 2, 5, 6), (2, 5, 4, 5), (0, 0, 2, 2), (12, 1, 3, 9)].iter() {
+        let mut world = World::create(16, 16, 8);
+        let mut expected: Vec<u32> = centers(x1, y1, x2, y2)
+            .into_iter()
+            .map(|(x, y)| (y * 16 + x) as u32)
+            .collect();
+
+        expected.sort_unstable();
+        expected.dedup();
+
+        world.paint(x1, y1, x2, y2, 0, Material::Rock, Tint::None, 0);
+
+        assert_eq!(world.preview_paint(x1, y1, x2, y2, 0), expected);
+        assert_eq!(world.particle_count(), expected.len() as u32);
+    }
+}
+
+#[test]
+fn retuning_spread_globally_changes_how_far_liquids_flow() {
+    let front = |spread: u8| {
+        let mut world = world();
+
+        world.fill_rect(0, 56, 16, 8, Material::Water, Tint::None, 0);
+        world.fill_rect(40, 56, 1, 8, Material::Oil, Tint::None, 0);
+        world.step(10);
+        world.set_spread_global(Material::Water, spread);
+        world.step(4);
+
+        assert!(world.get(40, 56) == Some(&Material::Oil));
+
+        (0..64)
+            .filter(|&x| world.get(x, 63) == Some(&Material::Water))
+            .max()
+            .unwrap()
+    };
+
+    assert_eq!(front(0), 15);
+    assert!(front(1) > 15);
+    assert!(front(6) > front(1));
+}
+
+#[test]
+fn chunk_activity_is_refreshed_in_place_every_tick() {
+    let mut world = world();
+    let pointer = world.chunk_activity();
+
+    assert_eq!(world.chunk_activity_len(), 16);
+    assert!(world.chunk_activity_slice().iter().all(|&state| state == 0));
+
+    world.place(40, 8, Material::Sand, Tint::None, 0);
+    world.simulate();
+    assert_eq!(
+        world.chunk_activity_slice(),
+        &[0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    world.step(7);
+    assert_eq!(
+        world.chunk_activity_slice(),
+        &[0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 0, 0, 0]
+    );
+    world.simulate();
+    assert_eq!(
+        world.chunk_activity_slice(),
+        &[0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 0]
+    );
+
+    world.step(200);
+
+    assert!(world.chunk_activity_slice().iter().all(|&state| state == 0));
+    assert_eq!(world.chunk_activity(), pointer);
+}
+
+#[test]
+fn undo_and_redo_step_through_edits_but_not_ticks() {
+    let mut world = world();
+    let mut states = vec![world.materials_slice().to_vec()];
+
+    world.enable_history(2);
+
+    for &y in [8, 16, 24].iter() {
+        world.paint(4, y, 60, y, 1, Material::Rock, Tint::None, 0);
+        world.step(4);
+        states.push(world.materials_slice().to_vec());
+    }
+
+    assert!(world.undo());
+    assert!(world.undo());
+    assert!(!world.undo());
+    assert!(world.materials_slice() == &states[1][..]);
+
+    assert!(world.redo());
+    assert!(world.materials_slice() == &states[2][..]);
+
+    world.place(0, 0, Material::Rock, Tint::None, 0);
+
+    assert!(!world.redo());
+}
+
+#[test]
+fn changes_are_listed_once_and_reset_every_tick() {
+    let mut world = world();
+
+    world.place(10, 10, Material::Sand, Tint::None, 0);
+    world.set_tint(10, 10, Tint::Darker);
+
+    assert_eq!(world.changes_slice(), &[10 * 64 + 10]);
+
+    world.simulate();
+
+    let mut changes = world.changes_slice().to_vec();
+
+    changes.sort_unstable();
+
+    assert_eq!(changes, vec![10 * 64 + 10, 11 * 64 + 10]);
+
+    world.fill_rect(40, 60, 4, 4, Material::Rock, Tint::None, 0);
+    world.step(4);
+
+    assert!(!world.changes_slice().is_empty());
+    assert!(world.changes_slice().iter().all(|&index| index % 64 == 10));
+}
+
+#[test]
+fn cold_pools_freeze_from_the_floor_up() {
+    let mut world = world();
+    let ice = |world: &World, y: usize| {
+        (0..64)
+            .filter(|&x| world.get(x, y) == Some(&Material::Ice))
+            .count()
+    };
+
+    world.fill_rect(0, 56, 64, 8, Material::Water, Tint::None, 0);
+    world.set_ambient_temperature(-10);
+    world.step(5);
+
+    assert!(ice(&world, 63) > 0);
+    assert_eq!(ice(&world, 56), 0);
+
+    for _ in 0..4 {
+        world.step(5);
+
+        for y in 57..64 {
+            assert!(ice(&world, y - 1) <= ice(&world, y));
+        }
+    }
+
+    world.step(200);
+
+    assert_eq!(world.counts_slice()[Material::Ice as usize], 512);
+}
+
+#[test]
+fn tick_reports_follow_an_avalanche_until_it_settles() {
+    let mut world = world();
+
+    world.fill_rect(20, 10, 10, 1, Material::Sand, Tint::None, 0);
+
+    let report = world.simulate();
+
+    assert_eq!(report.moved_cells, 10);
+    assert_eq!(report.active_chunks, world.active_chunk_count() as u32);
+    assert!(report.still_hot);
+
+    let mut last = report;
+    let mut ticks = 0;
+
+    while last.still_hot {
+        last = world.simulate();
+        ticks += 1;
+
+        assert!(ticks < 500);
+    }
+
+    assert_eq!(last.moved_cells, 0);
+    assert_eq!(world.step(10), 0);
+    assert_eq!(world.counts_slice()[Material::Sand as usize], 10);
+}
+
+#[test]
+fn active_cells_and_occupancy_are_counted() {
+    let mut world = world();
+
+    world.simulate();
+
+    assert_eq!(world.count_active_cells(), 0);
+    assert_eq!(world.non_air_count(), 0);
+
+    world.fill_rect(0, 60, 64, 4, Material::Rock, Tint::None, 0);
+    world.fill_rect(10, 20, 3, 1, Material::Sand, Tint::None, 0);
+    world.simulate();
+
+    assert_eq!(world.count_active_cells(), 3);
+    assert_eq!(world.non_air_count(), 64 * 4 + 3);
+
+    world.step(100);
+    world.simulate();
+
+    assert_eq!(world.count_active_cells(), 0);
+    assert_eq!(world.non_air_count(), 64 * 4 + 3);
+}
+
+#[test]
+fn budgeted_ticks_match_whole_ticks() {
+    let scene = || {
+        let mut world = world();
+
+        world.fill_rect(0, 60, 64, 4, Material::Rock, Tint::None, 0);
+        world.fill_rect(10, 10, 20, 20, Material::Sand, Tint::None, 0);
+        world.fill_rect(36, 0, 20, 30, Material::Water, Tint::None, 3);
+        world
+    };
+
+    let mut whole = scene();
+    let mut budgeted = scene();
+    let mut calls = 0;
+
+    for _ in 0..30 {
+        whole.simulate();
+
+        loop {
+            calls += 1;
+
+            if budgeted.simulate_budgeted(0) {
+                break;
+            }
+
+            assert!(budgeted.tick() == whole.tick() - 1);
+        }
+
+        assert_eq!(budgeted.tick(), whole.tick());
+    }
+
+    assert!(calls > 30);
+    assert_eq!(budgeted.state_hash(), whole.state_hash());
+    assert!(budgeted.materials_slice() == whole.materials_slice());
+}
+
+#[test]
+fn every_material_is_classified() {
+    // (material, liquid, gas, flammable)
+    let expected = [
+        (Material::Air, false, true, false),
+        (Material::Rock, false, false, false),
+        (Material::Sand, false, false, false),
+        (Material::Water, true, false, false),
+        (Material::Smoke, false, true, false),
+        (Material::Oil, true, false, true),
+        (Material::Acid, true, false, false),
+        (Material::Steam, false, true, false),
+        (Material::Fire, false, true, false),
+        (Material::Fuse, false, false, true),
+        (Material::Virus, false, false, false),
+        (Material::Ice, false, false, false),
+    ];
+
+    assert_eq!(expected.len(), MATERIAL_COUNT);
+
+    for &(material, liquid, gas, flammable) in expected.iter() {
+        assert_eq!(is_liquid(material), liquid);
+        assert_eq!(is_gas(material), gas);
+        assert_eq!(is_flammable(material), flammable);
+    }
+}
+
+#[test]
+fn the_tick_counter_survives_replays_and_rewinds() {
+    let mut world = world();
+
+    assert_eq!(world.tick(), 0);
+
+    world.simulate();
+    world.simulate();
+    world.place(8, 8, Material::Sand, Tint::None, 0);
+
+    assert_eq!(world.step(5), 5);
+    assert_eq!(world.tick(), 7);
+
+    world.start_recording();
+    world.enable_rewind(4, 2);
+    world.step(4);
+
+    let replayed = World::replay(&world.stop_recording()).unwrap();
+
+    assert_eq!(world.tick(), 11);
+    assert_eq!(replayed.tick(), 11);
+
+    assert!(world.rewind(1));
+    assert_eq!(world.tick(), 11);
+    assert!(world.rewind(2));
+    assert_eq!(world.tick(), 9);
+
+    world.simulate();
+
+    assert_eq!(world.tick(), 10);
+
+    world.reset();
+
+    assert_eq!(world.tick(), 0);
+}
+
+#[test]
+fn fast_cells_land_on_thin_floors() {
+    for &substeps in [1, 4].iter() {
+        let mut world = world();
+
+        world.set_max_fall_speed(12);
+        world.set_substeps(substeps);
+        world.fill_rect(0, 40, 64, 1, Material::Rock, Tint::None, 0);
+        world.place(20, 0, Material::Sand, Tint::None, 0);
+
+        let mut lowest = 0;
+
+        for _ in 0..40 {
+            world.simulate();
+
+            if let Some((_, y)) = find(&world, Material::Sand) {
+                lowest = lowest.max(y);
+            }
+        }
+
+        assert_eq!(lowest, 39);
+        assert!(world.get(20, 39) == Some(&Material::Sand));
+        assert_eq!(world.counts_slice()[Material::Rock as usize], 64);
+    }
+}
+
+#[test]
+fn paused_materials_hold_still_but_make_way() {
+    let mut world = world();
+
+    world.fill_rect(20, 20, 8, 4, Material::Water, Tint::None, 4);
+    world.set_material_paused(Material::Water, true);
+    world.fill_rect(22, 10, 4, 1, Material::Sand, Tint::None, 0);
+    world.step(200);
+
+    assert_eq!(world.step(10), 0);
+    assert!(world.get(20, 20) == Some(&Material::Water));
+    assert!(world.get(27, 23) == Some(&Material::Water));
+    assert_eq!(
+        (0..64)
+            .filter(|&x| world.get(x, 63) == Some(&Material::Sand))
+            .count(),
+        4
+    );
+
+    world.set_material_paused(Material::Water, false);
+
+    assert_eq!(world.step(1), 1);
+    assert!(world.get(20, 20) == Some(&Material::Air));
+
+    world.step(300);
+
+    assert_eq!(world.counts_slice()[Material::Water as usize], 32);
+    assert!((20..24).all(|y| (0..64).all(|x| world.get(x, y) != Some(&Material::Water))));
+}
+
+#[test]
+fn flooding_keeps_solids_and_wakes_the_world() {
+    let mut world = world();
+
+    world.fill_rect(0, 48, 32, 16, Material::Rock, Tint::None, 0);
+    world.place(48, 10, Material::Sand, Tint::None, 0);
+    world.step(100);
+
+    assert_eq!(world.active_chunk_count(), 0);
+
+    world.fill_air(Material::Water, Tint::None, 4);
+
+    assert_eq!(world.active_chunk_count(), 16);
+    assert_eq!(world.counts_slice()[Material::Rock as usize], 32 * 16);
+    assert_eq!(
+        world.counts_slice()[Material::Water as usize],
+        64 * 64 - 32 * 16 - 1
+    );
+    assert!(world.get(48, 63) == Some(&Material::Sand));
+    assert_eq!(world.step(1), 1);
+}
+
+#[test]
+fn debug_steps_walk_a_tick_one_chunk_row_at_a_time() {
+    let scene = || {
+        let mut world = world();
+
+        world.fill_rect(10, 2, 8, 40, Material::Sand, Tint::None, 0);
+        world.fill_rect(40, 20, 12, 12, Material::Water, Tint::None, 3);
+        world
+    };
+
+    let mut whole = scene();
+    let mut stepped = scene();
+
+    whole.simulate();
+
+    let mut rows = Vec::new();
+
+    loop {
+        let info = stepped.debug_step();
+
+        assert_eq!(info.swaps().len() % 2, 0);
+        assert!(info
+            .visited()
+            .iter()
+            .all(|&index| { index as usize / 64 / 16 == info.chunk_row() }));
+
+        rows.push(info.chunk_row());
+
+        if info.complete() {
+            break;
+        }
+    }
+
+    assert_eq!(rows, vec![3, 2, 1, 0]);
+    assert!(stepped.materials_slice() == whole.materials_slice());
+    assert_eq!(stepped.state_hash(), whole.state_hash());
+}
+
+#[test]
+fn a_recorded_session_replays_to_the_same_world() {
+    let mut world = World::create(48, 32, 8);
+
+    world.set_seed(11);
+    world.start_recording();
+    world.paint(0, 28, 47, 31, 2, Material::Rock, Tint::None, 0);
+
+    for i in 0..20 {
+        world.place(10 + i, 0, Material::Sand, Tint::Dark, 1);
+        world.place(30 + i % 8, 2, Material::Water, Tint::None, 4);
+        world.simulate();
+    }
+
+    world.step(50);
+
+    let log = world.stop_recording();
+    let replayed = World::replay(&log).unwrap();
+
+    assert_eq!(replayed.state_hash(), world.state_hash());
+    assert_eq!(replayed.tick(), world.tick());
+    assert!(World::replay(&log[..log.len() / 2]).is_err());
+    assert!(World::replay(&[]).is_err());
+}
+
+#[test]
+fn chunk_stats_count_the_moves_started_in_each_chunk() {
+    let mut world = world();
+
+    assert_eq!(world.chunk_stats_len(), 0);
+
+    world.enable_chunk_stats(true);
+    world.fill_rect(2, 2, 3, 1, Material::Sand, Tint::None, 0);
+    world.place(50, 40, Material::Sand, Tint::None, 0);
+
+    let report = world.simulate();
+    let stats = world.chunk_stats_slice();
+
+    assert_eq!(stats.len(), 16);
+    assert_eq!(stats[0], 3);
+    assert_eq!(stats[2 * 4 + 3], 1);
+    assert_eq!(
+        stats.iter().map(|&moves| moves as u32).sum::<u32>(),
+        report.moved_cells
+    );
+
+    world.step(100);
+    world.simulate();
+
+    assert!(world.chunk_stats_slice().iter().all(|&moves| moves == 0));
+
+    world.enable_chunk_stats(false);
+
+    assert_eq!(world.chunk_stats_len(), 0);
+}
+
+#[test]
+fn only_flowing_water_slowly_wears_rock_away() {
+    let carved = 64 * 56 - 3 * 56;
+    let mut world = world();
+
+    world.fill_rect(0, 8, 64, 56, Material::Rock, Tint::None, 0);
+    pour_down_shaft(&mut world, 10);
+
+    assert_eq!(rock_count(&world), carved);
+
+    world.set_erosion(true);
+    world.reset_region(30, 0, 3, 64);
+    world.fill_rect(30, 8, 3, 56, Material::Water, Tint::None, 5);
+    world.step(600);
+
+    assert_eq!(rock_count(&world), carved);
+
+    let mut worn = Vec::new();
+
+    for _ in 0..4 {
+        pour_down_shaft(&mut world, 5);
+        worn.push(carved - rock_count(&world));
+    }
+
+    assert!(worn.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(worn[3] > 0 && worn[3] < 56);
+}
+
+#[test]
+fn rebuilding_chunks_wakes_everything_that_is_not_air() {
+    let mut world = world();
+
+    world.fill_rect(0, 60, 64, 4, Material::Rock, Tint::None, 0);
+    world.fill_rect(4, 4, 6, 6, Material::Sand, Tint::None, 0);
+    world.step(300);
+    world.place(40, 20, Material::Sand, Tint::None, 0);
+    world.simulate_budgeted(0);
+    world.rebuild_chunks(8);
+
+    assert_eq!((world.chunk_columns(), world.chunk_rows()), (8, 8));
+    assert_eq!(world.chunk_size(), 8);
+    assert_eq!(world.chunk_activity_len(), 64);
+
+    let occupied = (0..64)
+        .filter(|&chunk| {
+            let (cx, cy) = (chunk % 8, chunk / 8);
+
+            (0..8)
+                .any(|j| (0..8).any(|i| world.get(cx * 8 + i, cy * 8 + j) != Some(&Material::Air)))
+        })
+        .count();
+
+    assert_eq!(world.active_chunk_count(), occupied);
+
+    world.step(300);
+
+    assert!(world.get(40, 59) == Some(&Material::Sand));
+    assert_eq!(world.counts_slice()[Material::Sand as usize], 37);
+    assert_eq!(world.active_chunk_count(), 0);
+}
+
+#[test]
+fn small_worlds_export_as_csv() {
+    let mut world = World::create(3, 3, 2);
+
+    world.place(0, 0, Material::Rock, Tint::None, 0);
+    world.place(1, 1, Material::Water, Tint::None, 0);
+    world.place(2, 2, Material::Oil, Tint::None, 0);
+
+    assert_eq!(world.export_csv().unwrap(), "1,0,0\n0,3,0\n0,0,5\n");
+    assert!(World::create(257, 256, 16).export_csv().is_err());
+}
+
+#[test]
+fn double_buffering_levels_a_pool_completely() {
+    for &mode in [UpdateMode::InPlace, UpdateMode::DoubleBuffered].iter() {
+        let mut world = world();
+
+        world.set_update_mode(mode);
+        world.fill_rect(24, 0, 16, 8, Material::Water, Tint::None, 5);
+        world.step(300);
+
+        let water = |y: usize| {
+            (0..64)
+                .filter(|&x| world.get(x, y) == Some(&Material::Water))
+                .count()
+        };
+
+        assert_eq!(water(63), 64);
+        assert_eq!(water(61) + water(62), 64);
+        assert_eq!(world.counts_slice()[Material::Water as usize], 128);
+
+        // Moving in place, a cell can be left perched on its neighbors before it gets a turn.
+        if mode == UpdateMode::DoubleBuffered {
+            assert_eq!(water(62), 64);
+        }
+    }
+}
+
+#[test]
+fn color_overrides_follow_their_cells() {
+    let mut world = world();
+
+    world.place(10, 10, Material::Sand, Tint::None, 0);
+    world.place(11, 10, Material::Sand, Tint::None, 0);
+    world.set_cell_color_override(10, 10, 0xff00_00ff);
+    world.set_cell_color_override(12, 10, 0x00ff_00ff);
+    world.step(200);
+
+    assert_eq!(world.cell_color_override(10, 63), Some(0xff00_00ff));
+    assert_eq!(world.cell_color_override(11, 63), None);
+    assert_eq!(world.cell_color_override(10, 10), None);
+    assert_eq!(
+        world.color_overrides(),
+        vec![10 * 64 + 12, 0x00ff_00ff, 63 * 64 + 10, 0xff00_00ff]
+    );
+
+    world.clear_cell_color_override(12, 10);
+    world.place(10, 63, Material::Rock, Tint::None, 0);
+
+    assert!(world.color_overrides().is_empty());
+}
+
+#[test]
+fn substeps_add_up_to_a_single_frame() {
+    let mut world = world();
+
+    world.set_max_fall_speed(1);
+    world.set_substeps(3);
+    world.place(10, 10, Material::Sand, Tint::None, 0);
+
+    let report = world.simulate();
+    let mut changes = world.changes_slice().to_vec();
+
+    changes.sort_unstable();
+
+    assert_eq!(report.moved_cells, 3);
+    assert_eq!(world.tick(), 3);
+    assert_eq!(
+        changes,
+        vec![10 * 64 + 10, 11 * 64 + 10, 12 * 64 + 10, 13 * 64 + 10]
+    );
+    assert_eq!(find(&world, Material::Sand), Some((10, 13)));
+}
+
+#[test]
+fn trapped_steam_finds_the_only_way_out() {
+    let mut world = world();
+
+    world.fill_rect(0, 20, 64, 2, Material::Rock, Tint::None, 0);
+    world.place(58, 20, Material::Air, Tint::None, 0);
+    world.place(58, 21, Material::Air, Tint::None, 0);
+    world.fill_rect(2, 40, 8, 4, Material::Steam, Tint::None, 3);
+
+    let escaped = |world: &World| {
+        (0..20)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|&(x, y)| world.get(x, y) == Some(&Material::Steam))
+            .count()
+    };
+
+    world.step(50);
+
+    assert_eq!(escaped(&world), 0);
+
+    world.step(350);
+
+    assert_eq!(escaped(&world), 32);
+
+    world.step(500);
+
+    assert_eq!(escaped(&world), 32);
+}
+
+#[test]
+fn the_idle_callback_fires_once_per_settle() {
+    let calls = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut world = world();
+
+    world.idle_callback = Some(Callback::Log(calls.clone()));
+    world.simulate();
+
+    assert_eq!(calls.borrow().len(), 0);
+
+    world.place(10, 10, Material::Sand, Tint::None, 0);
+    world.simulate();
+
+    assert_eq!(calls.borrow().len(), 0);
+
+    world.step(200);
+    world.simulate();
+    world.simulate();
+
+    assert_eq!(calls.borrow().len(), 1);
+
+    world.place(20, 10, Material::Sand, Tint::None, 0);
+    world.step(200);
+
+    assert_eq!(calls.borrow().len(), 2);
+
+    world.clear_idle_callback();
+    world.place(30, 10, Material::Sand, Tint::None, 0);
+    world.step(200);
+
+    assert_eq!(calls.borrow().len(), 2);
+}
+
+#[test]
+fn resetting_a_region_lets_the_sand_around_it_collapse() {
+    let mut world = world();
+
+    world.fill_rect(0, 24, 64, 40, Material::Sand, Tint::None, 1);
+    world.step(100);
+
+    assert_eq!(world.active_chunk_count(), 0);
+
+    world.reset_region(24, 40, 16, 16);
+    world.reset_region(60, 70, 10, 10);
+
+    assert_eq!(
+        world.counts_slice()[Material::Sand as usize],
+        64 * 40 - 16 * 16
+    );
+    assert!(world.active_chunk_count() > 0);
+
+    world.step(400);
+
+    assert!((0..64).all(|x| world.get(x, 63) == Some(&Material::Sand)));
+    assert!((40..56).all(|y| (24..40).all(|x| world.get(x, y) == Some(&Material::Sand))));
+    assert!(world.get(32, 24) == Some(&Material::Air));
+}
+
+#[test]
+fn cell_callbacks_get_a_batch_of_events_per_tick() {
+    let calls = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut world = world();
+
+    world.place(5, 5, Material::Water, Tint::None, 0);
+    world.watch(Material::Water, Callback::Log(calls.clone()));
+    world.place(10, 10, Material::Water, Tint::None, 0);
+    world.place(11, 10, Material::Water, Tint::None, 0);
+    world.place(11, 10, Material::Air, Tint::None, 0);
+    world.place(5, 5, Material::Sand, Tint::None, 0);
+
+    assert!(calls.borrow().is_empty());
+
+    world.simulate();
+
+    // Water that came and went between ticks, or only passed through a cell, goes unreported.
+    assert_eq!(*calls.borrow(), vec![vec![5, 5, 0, 10, 11, 1]]);
+
+    world.simulate();
+
+    assert_eq!(calls.borrow()[1], vec![10, 11, 0, 10, 12, 1]);
+
+    world.step(200);
+    world.simulate();
+
+    let settled = calls.borrow().len();
+
+    world.clear_cell_callback(Material::Water);
+    world.place(20, 20, Material::Water, Tint::None, 0);
+    world.step(200);
+
+    assert_eq!(calls.borrow().len(), settled);
+    assert!(world.watched.is_empty());
+}
+
+#[test]
+fn gradient_strokes_darken_one_level_at_a_time() {
+    let mut world = World::create(8, 8, 8);
+
+    for (x, &tint) in Tint::LEVELS.iter().enumerate() {
+        world.paint(x, 0, x, 8, 0, Material::Rock, tint, 0);
+    }
+
+    let levels: Vec<u8> = (0..8)
+        .map(|x| tint_level(world.tints_slice()[7 * 8 + x]))
+        .collect();
+
+    assert_eq!(levels, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+
+    // The original four tints alone jump two levels at a time.
+    let coarse = [Tint::None, Tint::Dark, Tint::Darker, Tint::Darkest];
+
+    assert!(coarse
+        .windows(2)
+        .all(|pair| tint_level(pair[1]) - tint_level(pair[0]) == 2));
+}
+
+#[test]
+fn the_chunk_list_covers_edits_and_scanned_chunks() {
+    let mut world = world();
+    let listed = |world: &World| {
+        let mut list = world.active_chunk_list_slice().to_vec();
+
+        list.sort_unstable();
+        list
+    };
+
+    // The list is put together at the end of a tick.
+    world.place(40, 40, Material::Rock, Tint::None, 0);
+
+    assert!(listed(&world).is_empty());
+
+    world.simulate();
+
+    assert_eq!(listed(&world), vec![5, 6, 7, 9, 10, 11, 13, 14, 15]);
+
+    world.simulate();
+
+    assert!(listed(&world).is_empty());
+
+    world.place(2, 2, Material::Sand, Tint::None, 0);
+    world.simulate();
+
+    assert_eq!(listed(&world), vec![0, 1, 4, 5]);
+    assert_eq!(world.active_chunk_list_len(), 4);
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn only_solid_cells_in_active_chunks_are_scanned() {
+    let mut world = world();
+
+    world.fill_rect(0, 63, 64, 1, Material::Rock, Tint::None, 0);
+    world.fill_rect(2, 50, 3, 1, Material::Sand, Tint::None, 0);
+    world.simulate();
+
+    assert_eq!(world.sim_stats().cells_scanned, 64 + 3);
+
+    // Only the chunks around the falling sand are still awake.
+    world.simulate();
+
+    assert_eq!(world.sim_stats().cells_scanned, 32 + 3);
+
+    world.step(100);
+    world.simulate();
+
+    assert_eq!(world.sim_stats().cells_scanned, 0);
+}
+
+#[test]
+fn strokes_stop_partway_at_the_particle_limit() {
+    let mut world = world();
+
+    world.set_particle_limit(20);
+    world.paint(0, 10, 63, 10, 0, Material::Rock, Tint::None, 0);
+
+    assert_eq!(world.particle_count(), 20);
+    assert!(world.get(19, 10) == Some(&Material::Rock));
+    assert!(world.get(20, 10) == Some(&Material::Air));
+
+    // Replacing material that is already there does not count against the limit.
+    world.paint(0, 10, 10, 10, 0, Material::Sand, Tint::None, 0);
+    world.place(40, 40, Material::Water, Tint::None, 0);
+
+    assert_eq!(world.counts_slice()[Material::Sand as usize], 10);
+    assert_eq!(world.counts_slice()[Material::Water as usize], 0);
+    assert_eq!(world.particle_count(), 20);
+
+    world.place(0, 10, Material::Air, Tint::None, 0);
+    world.place(40, 40, Material::Water, Tint::None, 0);
+
+    assert_eq!(world.counts_slice()[Material::Water as usize], 1);
+    assert_eq!(world.particle_count(), 20);
+}
+
+#[test]
+fn placements_set_their_temperature() {
+    let mut world = world();
+    let temperature = |world: &World, x: usize, y: usize| world.temperatures_slice()[y * 64 + x];
+
+    world.place_with_temp(10, 63, Material::Fire, Tint::None, 0, 1200);
+    world.place_with_temp(20, 63, Material::Ice, Tint::None, 0, -40);
+    world.place(30, 63, Material::Ice, Tint::None, 0);
+    world.place(40, 63, Material::Rock, Tint::None, 0);
+
+    assert_eq!(temperature(&world, 10, 63), 1200);
+    assert_eq!(temperature(&world, 20, 63), -40);
+    assert_eq!(temperature(&world, 30, 63), ICE_TEMPERATURE);
+    assert_eq!(temperature(&world, 40, 63), DEFAULT_AMBIENT_TEMPERATURE);
+
+    // Nothing is placed over the limit, so nothing is heated either.
+    world.set_particle_limit(4);
+    world.place_with_temp(50, 63, Material::Rock, Tint::None, 0, 500);
+
+    assert!(world.get(50, 63) == Some(&Material::Air));
+    assert_eq!(temperature(&world, 50, 63), DEFAULT_AMBIENT_TEMPERATURE);
+}
+
+#[test]
+fn material_counts_match_a_scan_after_random_edits() {
+    let mut world = world();
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as usize
+    };
+
+    for _ in 0..3000 {
+        let material = Material::try_from(next(MATERIAL_COUNT) as u8).unwrap();
+        let (x, y) = (next(64), next(64));
+
+        match next(5) {
+            0 => {
+                world.place(x, y, material, Tint::None, 3);
+            }
+            1 => {
+                world.paint(x, y, next(64), next(64), next(3), material, Tint::None, 3);
+            }
+            2 => {
+                world.fill_rect(x, y, next(8), next(8), material, Tint::None, 3);
+            }
+            3 => {
+                world.reset_region(x, y, next(8), next(8));
+            }
+            _ => {
+                world.simulate();
+            }
+        }
+    }
+
+    let mut scanned = [0; MATERIAL_COUNT];
+
+    for &material in world.materials_slice() {
+        scanned[material as usize] += 1;
+    }
+
+    assert_eq!(world.counts_slice(), &scanned[..]);
+    assert_eq!(
+        world.particle_count(),
+        64 * 64 - scanned[Material::Air as usize]
+    );
+}
+
+#[test]
+fn the_spread_cap_limits_how_far_a_cell_flows_per_tick() {
+    // Liquid slides into air without being marked dirty, so the drop starts at the end of the row
+    // the scan reaches first, where it cannot be visited twice in one tick.
+    let flow = |cap: u8| {
+        let mut world = world();
+
+        world.set_max_spread_per_tick(cap);
+        world.fill_rect(24, 63, 40, 1, Material::Water, Tint::None, 0);
+        world.place(63, 62, Material::Water, Tint::None, 255);
+        world.simulate();
+
+        let drop = (0..64).find(|&x| world.get(x, 62) == Some(&Material::Water));
+
+        (drop, world.get(23, 63) == Some(&Material::Water))
+    };
+
+    assert_eq!(flow(255), (None, true));
+    assert!(flow(8).0 >= Some(55));
+    assert_eq!(flow(1), (Some(62), false));
+}
+
+#[cfg(feature = "debug-checks")]
+#[test]
+fn invariants_hold_through_a_busy_scene_and_name_what_broke() {
+    let mut world = world();
+
+    world.fill_rect(0, 56, 64, 8, Material::Rock, Tint::None, 0);
+    world.fill_rect(4, 10, 20, 20, Material::Sand, Tint::None, 1);
+    world.fill_rect(30, 0, 20, 30, Material::Water, Tint::None, 4);
+    world.fill_rect(52, 40, 4, 4, Material::Oil, Tint::None, 3);
+    world.place(53, 39, Material::Fire, Tint::None, 0);
+
+    for _ in 0..100 {
+        world.simulate();
+        world.check_invariants().unwrap();
+    }
+
+    world.simulate_budgeted(0);
+    world.check_invariants().unwrap();
+    world.finish_tick();
+
+    world.counts[Material::Sand as usize] += 1;
+
+    assert_eq!(
+        world.check_invariants().unwrap_err(),
+        "the count of material 2 is 401, but the world holds 400"
+    );
+
+    world.recount();
+    world.dirty.mark(3 * 64 + 2);
+
+    assert_eq!(
+        world.check_invariants().unwrap_err(),
+        "cell (2, 3) is dirty between ticks"
+    );
+}
+
+#[test]
+fn lighter_gases_rise_through_heavier_ones() {
+    let mut world = World::create(16, 32, 8);
+
+    // A sealed jar with smoke sitting on top of steam.
+    world.fill_rect(0, 0, 16, 32, Material::Rock, Tint::None, 0);
+    world.fill_rect(4, 8, 8, 16, Material::Air, Tint::None, 0);
+    world.fill_rect(4, 8, 8, 8, Material::Smoke, Tint::None, 3);
+    world.fill_rect(4, 16, 8, 8, Material::Steam, Tint::None, 3);
+    world.step(300);
+
+    let layer = |world: &World, top: usize| {
+        (top..top + 8)
+            .flat_map(|y| (4..12).map(move |x| (x, y)))
+            .filter(|&(x, y)| world.get(x, y) == Some(&Material::Steam))
+            .count()
+    };
+
+    assert_eq!(layer(&world, 8), 64);
+    assert_eq!(layer(&world, 16), 0);
+    assert_eq!(world.counts_slice()[Material::Smoke as usize], 64);
+}
+
+#[test]
+fn state_hashes_are_pinned_and_cover_every_channel() {
+    let mut world = World::create(4, 2, 2);
+
+    assert_eq!(world.state_hash(), 0xa4a1_4e22_1c15_ed83);
+    assert_ne!(World::create(2, 4, 2).state_hash(), world.state_hash());
+
+    world.place(1, 1, Material::Rock, Tint::None, 0);
+
+    assert_eq!(world.state_hash(), 0xd1e6_7724_8847_3c18);
+
+    let mut hashes = vec![world.state_hash()];
+
+    world.set_tint(1, 1, Tint::Dark);
+    hashes.push(world.state_hash());
+    world.set_spread(1, 1, 3);
+    hashes.push(world.state_hash());
+    world.set_spread(1, 1, 0);
+    world.set_tint(1, 1, Tint::None);
+
+    assert_eq!(world.state_hash(), hashes[0]);
+    assert_ne!(hashes[0], hashes[1]);
+    assert_ne!(hashes[1], hashes[2]);
+}
+
+#[test]
+fn indices_and_coordinates_round_trip() {
+    let world = World::create(7, 5, 4);
+
+    assert_eq!(world.bounds(), (7, 5));
+
+    for y in 0..5 {
+        for x in 0..7 {
+            let index = world.index(x, y).unwrap();
+
+            assert_eq!(index, y * 7 + x);
+            assert_eq!(world.coords(index), Some((x, y)));
+        }
+    }
+
+    assert_eq!(world.index(7, 0), None);
+    assert_eq!(world.index(0, 5), None);
+    assert_eq!(world.index(usize::MAX, usize::MAX), None);
+    assert_eq!(world.coords(7 * 5 - 1), Some((6, 4)));
+    assert_eq!(world.coords(7 * 5), None);
+    assert_eq!(world.coords(usize::MAX), None);
+}
+
+#[test]
+fn burning_oil_spreads_across_the_slick_and_smokes() {
+    let mut world = world();
+
+    world.fill_rect(0, 56, 64, 8, Material::Water, Tint::None, 0);
+    world.fill_rect(0, 54, 64, 2, Material::Oil, Tint::None, 0);
+    world.place(0, 53, Material::Fire, Tint::None, 0);
+
+    let mut smoked = false;
+    let mut reached = false;
+
+    for _ in 0..200 {
+        world.simulate();
+
+        smoked |= world.counts_slice()[Material::Smoke as usize] > 0;
+        reached |= (48..64).any(|x| world.get(x, 54) == Some(&Material::Fire));
+    }
+
+    assert!(smoked && reached);
+    assert_eq!(world.counts_slice()[Material::Oil as usize], 0);
+    assert_eq!(world.counts_slice()[Material::Water as usize], 512);
+}
+
+#[test]
+fn rewinding_restores_recent_snapshots_and_resumes() {
+    let mut world = world();
+    let mut states = Vec::new();
+
+    world.enable_rewind(3, 10);
+    world.fill_rect(10, 0, 8, 8, Material::Sand, Tint::None, 1);
+
+    for _ in 0..5 {
+        world.step(10);
+        states.push(world.materials_slice().to_vec());
+    }
+
+    let frames = &world.rewind.as_ref().unwrap().frames;
+
+    assert_eq!(frames.len(), 3);
+    assert!(frames.iter().all(|frame| frame.packed.len() < 64 * 64));
+
+    // The most recent snapshot is the world as it is now, so three steps go back 20 ticks.
+    assert!(world.rewind(3));
+    assert!(world.materials_slice() == &states[2][..]);
+    assert_eq!(world.tick(), 30);
+
+    world.step(20);
+
+    assert!(world.materials_slice() == &states[4][..]);
+    assert!(!World::create(8, 8, 4).rewind(1));
+}
+
+#[test]
+fn simulation_bounds_confine_every_tick() {
+    let mut world = world();
+
+    world.set_simulation_bounds(10, 5, 30, 30);
+    world.place(20, 5, Material::Sand, Tint::None, 0);
+    world.place(5, 5, Material::Sand, Tint::None, 0);
+    world.place(45, 20, Material::Water, Tint::None, 4);
+    // Water on the edge of the window cannot leave it either.
+    world.place(39, 20, Material::Water, Tint::None, 4);
+
+    for _ in 0..10 {
+        world.step(20);
+
+        assert!(world.get(5, 5) == Some(&Material::Sand));
+        assert!(world.get(45, 20) == Some(&Material::Water));
+    }
+
+    assert!(world.get(20, 34) == Some(&Material::Sand));
+    assert!((10..40).any(|x| world.get(x, 34) == Some(&Material::Water)));
+    assert_eq!(world.counts_slice()[Material::Water as usize], 2);
+
+    world.clear_simulation_bounds();
+    world.step(200);
+
+    assert!(world.get(5, 63) == Some(&Material::Sand));
+    assert!(world.get(20, 63) == Some(&Material::Sand));
+    assert!(world.get(45, 20) == Some(&Material::Air));
+}
+
+#[test]
+fn strokes_longer_than_the_world_paint_both_ends() {
+    let mut world = World::create(16, 16, 8);
+
+    world.paint(3, 0, 3, 60, 0, Material::Rock, Tint::None, 0);
+    world.paint(5, 60, 5, 0, 1, Material::Rock, Tint::None, 0);
+    world.paint(60, 9, 0, 9, 1, Material::Rock, Tint::None, 0);
+
+    for &x in [3, 5].iter() {
+        assert!((0..16).all(|y| world.get(x, y) == Some(&Material::Rock)));
+    }
+
+    assert!((0..16).all(|x| world.get(x, 9) == Some(&Material::Rock)));
+}
+
+#[test]
+fn bitsets_pack_flags_across_word_boundaries() {
+    let mut bits = BitSet::new(130);
+
+    assert_eq!(bits.len(), 130);
+    assert!(!bits.any());
+
+    for &index in [0, 63, 64, 129, 130, 1000].iter() {
+        bits.set(index, true);
+    }
+
+    assert_eq!(bits.iter().collect::<Vec<_>>(), vec![0, 63, 64, 129]);
+    assert_eq!(bits.count(), 4);
+    assert!(bits.get(64) && !bits.get(65) && !bits.get(130));
+    assert!(bits.any_in(60, 64));
+    assert!(!bits.any_in(1, 63));
+    assert!(!bits.any_in(65, 129));
+    assert!(bits.any_in(65, 500));
+    assert!(!bits.any_in(64, 64));
+
+    bits.set(63, false);
+
+    assert!(!bits.any_in(1, 64));
+
+    bits.fill();
+
+    assert_eq!(bits.count(), 130);
+    assert_eq!(bits.iter().last(), Some(129));
+
+    bits.clear();
+
+    assert!(!bits.any());
+}
+
+#[test]
+fn the_original_materials_keep_their_behavior() {
+    let expected = [
+        (Material::Air, State::Gas, Behavior::Static),
+        (Material::Rock, State::Solid, Behavior::Static),
+        (Material::Sand, State::Solid, Behavior::Powder),
+        (Material::Water, State::Liquid, Behavior::Liquid),
+        (Material::Smoke, State::Gas, Behavior::Gas),
+    ];
+
+    for &(material, state, behavior) in expected.iter() {
+        assert!(material.props().state == state);
+        assert!(material.props().behavior == behavior);
+    }
+
+    // Heavier materials sink through lighter ones in the order they always have.
+    assert!(Material::Sand.sinks_into(Material::Water));
+    assert!(Material::Water.sinks_into(Material::Smoke));
+    assert!(Material::Water.sinks_into(Material::Air));
+    assert!(!Material::Sand.sinks_into(Material::Rock));
+    assert!(Material::Smoke.rises_through(Material::Air));
+}
+
+#[test]
+fn packed_cells_keep_up_with_the_world() {
+    let mut world = world();
+    let packed = |world: &mut World| {
+        let expected: Vec<u16> = world
+            .materials_slice()
+            .iter()
+            .zip(world.tints_slice())
+            .map(|(&material, &tint)| material as u16 | (tint as u16) << 8)
+            .collect();
+
+        world.cells_slice() == &expected[..]
+    };
+
+    world.place(3, 0, Material::Sand, Tint::Deepest, 0);
+
+    assert_eq!(world.cells_slice()[3], Material::Sand as u16 | 7 << 8);
+
+    world.fill_rect(20, 10, 10, 10, Material::Water, Tint::Dark, 3);
+    world.step(30);
+
+    assert!(packed(&mut world));
+
+    // Changing more cells than the world holds repacks everything at once.
+    for _ in 0..3 {
+        world.fill_rect(0, 0, 64, 64, Material::Rock, Tint::Darker, 0);
+        world.fill_rect(0, 0, 64, 64, Material::Air, Tint::None, 0);
+    }
+
+    world.place(9, 9, Material::Oil, Tint::Faint, 0);
+
+    assert!(packed(&mut world));
+
+    world.resize(32, 48, Anchor::TopLeft);
+
+    assert!(packed(&mut world));
+}
+
+#[test]
+fn warming_everything_rescans_a_settled_world() {
+    let mut world = world();
+
+    world.fill_rect(0, 48, 64, 16, Material::Sand, Tint::None, 1);
+    world.step(200);
+
+    assert_eq!(world.simulate().active_chunks, 0);
+
+    world.warm_all();
+
+    assert!(world.is_active());
+    assert_eq!(world.simulate().active_chunks, 16);
+
+    #[cfg(feature = "profiling")]
+    assert_eq!(world.sim_stats().cells_scanned, 64 * 16);
+
+    assert_eq!(world.step(10), 0);
+}
+
+#[test]
+fn scanning_only_active_chunks_matches_scanning_everything() {
+    let scene = || {
+        let mut world = World::create(96, 64, 16);
+
+        world.fill_rect(0, 56, 96, 8, Material::Rock, Tint::None, 0);
+        world.fill_rect(4, 30, 30, 20, Material::Sand, Tint::None, 1);
+        world.fill_rect(50, 10, 30, 20, Material::Water, Tint::None, 4);
+        world.step(150);
+
+        // Material dropped from a corner has to cross into chunks that went to sleep.
+        world.fill_rect(88, 0, 6, 6, Material::Sand, Tint::None, 1);
+        world.place(40, 0, Material::Water, Tint::None, 4);
+        world
+    };
+
+    let mut lazy = scene();
+    let mut eager = scene();
+
+    for _ in 0..300 {
+        lazy.simulate();
+        eager.warm_all();
+        eager.simulate();
+    }
+
+    assert_eq!(lazy.state_hash(), eager.state_hash());
+}
+
+#[test]
+fn falling_cells_are_flagged_as_moved_and_resting_ones_are_not() {
+    let mut world = world();
+
+    world.place(10, 63, Material::Sand, Tint::None, 0);
+    world.place(20, 10, Material::Sand, Tint::None, 0);
+
+    assert!(!world.motion.iter().any(|&moved| moved));
+
+    world.simulate();
+
+    let flagged: Vec<usize> = (0..64 * 64).filter(|&i| world.motion[i]).collect();
+
+    assert_eq!(flagged, vec![10 * 64 + 20, 11 * 64 + 20]);
+    assert!(!world.motion[63 * 64 + 10]);
+
+    world.step(100);
+    world.simulate();
+
+    assert!(!world.motion.iter().any(|&moved| moved));
+}
+
+#[test]
+fn dirty_marks_expire_with_their_generation_even_across_wraparound() {
+    let mut dirty = DirtyCells::new(4);
+
+    dirty.mark(1);
+
+    assert!(dirty.get(1) && !dirty.get(0));
+
+    dirty.clear();
+
+    assert!(!dirty.get(1));
+
+    // A mark left behind 255 generations ago must not come back once the counter wraps around.
+    dirty.mark(2);
+
+    for _ in 0..255 {
+        dirty.clear();
+
+        assert!(!dirty.get(2));
+    }
+
+    dirty.mark(3);
+
+    assert!(dirty.get(3) && !dirty.get(2));
+
+    dirty.clear();
+
+    assert!(!(0..4).any(|i| dirty.get(i)));
+}
+
+#[test]
+fn sand_placed_between_ticks_moves_on_the_next_one() {
+    let mut world = world();
+
+    for _ in 0..300 {
+        world.simulate();
+    }
+
+    world.place(5, 5, Material::Sand, Tint::None, 0);
+    world.simulate();
+
+    assert!(world.get(5, 6) == Some(&Material::Sand));
+}
+
+#[test]
+fn acid_neutralizes_in_deep_water_but_smokes_through_rock() {
+    let mut pool = world();
+
+    pool.fill_rect(0, 60, 64, 4, Material::Rock, Tint::None, 0);
+    pool.fill_rect(0, 20, 64, 40, Material::Water, Tint::None, 4);
+    pool.fill_rect(30, 16, 4, 4, Material::Acid, Tint::None, 3);
+    pool.step(600);
+
+    assert_eq!(pool.counts_slice()[Material::Acid as usize], 0);
+    assert_eq!(pool.counts_slice()[Material::Rock as usize], 64 * 4);
+    assert_eq!(pool.counts_slice()[Material::Water as usize], 64 * 40 + 16);
+
+    let mut dry = world();
+    let mut smoked = false;
+
+    dry.fill_rect(0, 48, 64, 16, Material::Rock, Tint::None, 0);
+    dry.fill_rect(30, 44, 4, 4, Material::Acid, Tint::None, 3);
+
+    for _ in 0..300 {
+        dry.simulate();
+        smoked |= dry.counts_slice()[Material::Smoke as usize] > 0;
+    }
+
+    assert!(smoked);
+    assert!(dry.counts_slice()[Material::Rock as usize] < 64 * 16);
+}
+
+#[test]
+fn batched_reads_mark_indices_outside_the_world() {
+    let mut world = World::create(4, 4, 2);
+
+    world.place(1, 0, Material::Sand, Tint::None, 0);
+    world.place(3, 3, Material::Water, Tint::None, 0);
+
+    assert_eq!(
+        world.get_many(&[0, 1, 15, 16, u32::MAX, 1]),
+        vec![
+            Material::Air as u8,
+            Material::Sand as u8,
+            Material::Water as u8,
+            255,
+            255,
+            Material::Sand as u8
+        ]
+    );
+    assert!(world.get_many(&[]).is_empty());
+}
+
+#[test]
+fn halving_the_tick_rate_moves_material_every_other_tick() {
+    let mut world = world();
+
+    world.set_max_fall_speed(1);
+    world.set_tick_rate_divisor(2);
+    world.place(10, 0, Material::Sand, Tint::None, 0);
+
+    let mut heights = Vec::new();
+
+    for _ in 0..6 {
+        world.simulate();
+        heights.push(find(&world, Material::Sand).unwrap().1);
+    }
+
+    assert_eq!(heights, vec![1, 1, 2, 2, 3, 3]);
+    assert_eq!(world.tick(), 6);
+
+    world.set_tick_rate_divisor(1);
+    world.step(2);
+
+    assert_eq!(find(&world, Material::Sand), Some((10, 5)));
+}
+
+#[test]
+fn chunk_neighborhoods_stop_at_the_edges_of_the_grid() {
+    let neighborhoods = Neighborhood::all(4, 3);
+
+    assert_eq!(neighborhoods.len(), 12);
+    assert_eq!(neighborhoods[0].chunks(), &[0, 1, 4, 5]);
+    assert_eq!(neighborhoods[3].chunks(), &[2, 3, 6, 7]);
+    assert_eq!(neighborhoods[5].chunks(), &[0, 1, 2, 4, 5, 6, 8, 9, 10]);
+    assert_eq!(neighborhoods[7].chunks(), &[2, 3, 6, 7, 10, 11]);
+    assert_eq!(neighborhoods[8].chunks(), &[4, 5, 8, 9]);
+    assert_eq!(neighborhoods[11].chunks(), &[6, 7, 10, 11]);
+
+    assert_eq!(Neighborhood::all(1, 1)[0].chunks(), &[0]);
+    assert_eq!(Neighborhood::all(3, 1)[1].chunks(), &[0, 1, 2]);
+
+    // Waking a cell in a corner chunk wakes its neighborhood, and nothing else.
+    let mut world = World::create(64, 48, 16);
+
+    world.place(63, 47, Material::Rock, Tint::None, 0);
+
+    let mut active: Vec<u32> = world.active_chunks.iter().map(|i| i as u32).collect();
+
+    active.sort_unstable();
+
+    assert_eq!(active, world.neighborhoods[11].chunks());
+}
+
+#[test]
+fn shrinking_to_the_world_leaves_no_spare_capacity() {
+    let mut world = world();
+
+    world.fill_rect(0, 32, 64, 32, Material::Sand, Tint::None, 1);
+    world.resize(16, 8, Anchor::BottomLeft);
+    world.shrink_to_world();
+
+    let cells = 16 * 8;
+
+    assert_eq!(world.materials.len(), cells);
+    assert_eq!(world.materials.capacity(), cells);
+    assert_eq!(world.tints.capacity(), cells);
+    assert_eq!(world.spreads.capacity(), cells);
+    assert_eq!(world.lives.capacity(), cells);
+    assert_eq!(world.velocities.capacity(), cells);
+    assert_eq!(world.temperatures.capacity(), cells);
+    assert_eq!(world.motion.capacity(), cells);
+    assert_eq!(world.dirty.generations.capacity(), cells);
+    assert_eq!(world.row_counts.capacity(), 8);
+
+    world.place(0, 0, Material::Water, Tint::None, 0);
+    world.fill_rect(0, 1, 16, 7, Material::Air, Tint::None, 0);
+    world.step(20);
+
+    assert!(world.get(0, 7) == Some(&Material::Water));
+}
+
+#[test]
+fn the_powder_liquid_and_gas_kernels_are_pinned() {
+    let run = |material: Material, y: usize| {
+        let mut world = World::create(48, 48, 16);
+
+        world.set_seed(5);
+        world.fill_rect(8, 46, 32, 1, Material::Rock, Tint::None, 0);
+        world.fill_rect(18, 2, 1, 1, Material::Rock, Tint::None, 0);
+        world.fill_rect(16, y, 12, 12, material, Tint::Dark, 3);
+        world.step(400);
+        world.state_hash()
+    };
+
+    // Any change to the shared sideways routine shows up in one of these.
+    assert_eq!(run(Material::Sand, 4), 0xb6089c3750717158);
+    assert_eq!(run(Material::Water, 4), 0x42aefb6b33cab92c);
+    assert_eq!(run(Material::Smoke, 30), 0xbefab05f9ef6cb72);
+}
+
+#[test]
+fn a_rightward_flow_bias_drifts_water_along_a_flat_trough() {
+    let run = |bias: i8| {
+        let mut trough = world();
+
+        trough.set_seed(3);
+        trough.set_flow_bias(bias);
+        trough.fill_rect(0, 63, 64, 1, Material::Rock, Tint::None, 0);
+        trough.fill_rect(28, 55, 8, 8, Material::Water, Tint::None, 5);
+
+        let mut out = vec![];
+        for ticks in [10, 30, 100] {
+            trough.step(ticks);
+            let (sum, n) = (0..64 * 64)
+                .filter(|&i| trough.materials_slice()[i] == Material::Water)
+                .fold((0, 0), |(sum, n), i| (sum + i % 64, n + 1));
+            out.push((sum as f64 / n as f64, n));
+        }
+        out
+    };
+
+    let level = run(0);
+    let tilted = run(127);
+
+    // The tilted drop heads right well before it reaches the walls, and stays to the right of an
+    // untilted one even once it has spread out.
+    assert!(tilted[0].0 > 31.5 + 5.0);
+
+    for (level, tilted) in level.iter().zip(tilted.iter()) {
+        assert_eq!(level.1, 64);
+        assert_eq!(tilted.1, 64);
+        assert!(tilted.0 > level.0);
+    }
+}
+
+#[test]
+fn changed_rects_merge_edited_chunks_and_reset_on_each_query() {
+    let mut world = world();
+
+    // A 2×2 block of chunks, plus one chunk off on its own.
+    for (x, y) in [(1, 1), (20, 2), (3, 30), (30, 30), (60, 60)] {
+        world.place(x, y, Material::Rock, Tint::None, 0);
+    }
+
+    assert_eq!(world.changed_rects(), vec![0, 0, 32, 32, 48, 48, 16, 16]);
+    assert!(world.changed_rects().is_empty());
+
+    // Sand falling inside one chunk only marks that chunk.
+    world.place(40, 4, Material::Sand, Tint::None, 1);
+    world.changed_rects();
+    world.simulate();
+
+    assert_eq!(world.changed_rects(), vec![32, 0, 16, 16]);
+}
+
+#[test]
+fn natural_sand_is_grainy_and_reproducible() {
+    let place = |seed: u64| {
+        let mut world = world();
+        let mut tints = Vec::new();
+
+        world.set_seed(seed);
+
+        for y in 0..16 {
+            for x in 0..16 {
+                world.place_natural(x, y, Material::Sand);
+                tints.push(world.tints_slice()[y * 64 + x]);
+            }
+        }
+
+        tints
+    };
+
+    let tints = place(7);
+    let mut seen = [0; 8];
+
+    for &tint in &tints {
+        seen[tint as usize] += 1;
+    }
+
+    // 256 grains land on every one of the 8 tints, none of them much more than the rest.
+    assert!(seen.iter().all(|&n| n > 0 && n < 64), "{:?}", seen);
+    assert!(place(7) == tints);
+    assert!(place(8) != tints);
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn tick_timings_split_a_busy_tick_and_stay_empty_when_idle() {
+    let mut world = world();
+
+    world.fill_rect(0, 0, 64, 32, Material::Sand, Tint::None, 1);
+
+    let stopwatch = Stopwatch::start();
+    world.simulate();
+    let elapsed = stopwatch.elapsed_micros();
+
+    let timings = world.tick_timings();
+
+    assert!(timings.scan_micros > 0.0);
+    assert!(timings.movement_micros > 0.0);
+    assert!(timings.bookkeeping_micros >= 0.0);
+    assert!(timings.scan_micros + timings.movement_micros + timings.bookkeeping_micros <= elapsed);
+
+    while world.is_active() {
+        world.simulate();
+    }
+
+    world.simulate();
+
+    let timings = world.tick_timings();
+
+    assert_eq!(timings.scan_micros, 0.0);
+    assert_eq!(timings.movement_micros, 0.0);
+}
+
+#[test]
+fn capped_ticks_get_around_to_every_chunk() {
+    let mut world = world();
+    let grains: Vec<(usize, usize)> = (0..16).map(|i| (i % 4 * 16 + 8, i / 4 * 16 + 2)).collect();
+
+    world.set_max_active_chunks(3);
+
+    for &(x, y) in &grains {
+        world.place(x, y, Material::Sand, Tint::None, 1);
+    }
+
+    let mut moved = [false; 16];
+
+    for _ in 0..6 {
+        assert!(world.simulate().active_chunks <= 3);
+
+        for (i, &(x, y)) in grains.iter().enumerate() {
+            moved[i] |= world.materials_slice()[y * 64 + x] == Material::Air;
+        }
+    }
+
+    // 16 chunks, 3 at a time, takes 6 ticks to see them all.
+    assert!(moved.iter().all(|&moved| moved), "{:?}", moved);
+}
+
+#[test]
+fn slices_line_up_with_the_pointer_getters() {
+    // 3×2 chunks, the last column and row of them cut short.
+    let mut world = World::create(40, 24, 16);
+
+    world.fill_rect(0, 0, 40, 4, Material::Sand, Tint::None, 1);
+    world.enable_chunk_stats(true);
+    world.simulate();
+
+    assert_eq!(world.materials_slice().len(), 40 * 24);
+    assert_eq!(world.tints_slice().len(), 40 * 24);
+    assert_eq!(world.temperatures_slice().len(), 40 * 24);
+    assert_eq!(world.moved_slice().len(), 40 * 24);
+    assert_eq!(world.counts_slice().len(), 12);
+    assert_eq!(world.chunk_activity_slice().len(), 6);
+    assert_eq!(world.chunk_stats_slice().len(), 6);
+
+    assert_eq!(world.materials_slice().as_ptr(), world.materials());
+    assert_eq!(world.tints_slice().as_ptr(), world.tints());
+    assert_eq!(world.temperatures_slice().as_ptr(), world.temperatures());
+    assert_eq!(world.moved_slice().as_ptr(), world.moved());
+    assert_eq!(world.counts_slice().as_ptr(), world.counts_ptr());
+    assert_eq!(world.changes_slice().as_ptr(), world.changes_ptr());
+    assert_eq!(
+        world.chunk_activity_slice().as_ptr(),
+        world.chunk_activity()
+    );
+    assert_eq!(
+        world.active_chunk_list_slice().as_ptr(),
+        world.active_chunk_list_ptr()
+    );
+    assert_eq!(world.chunk_stats_slice().as_ptr(), world.chunk_stats());
+
+    assert_eq!(world.changes_slice().len(), world.changes_len());
+    assert_eq!(
+        world.active_chunk_list_slice().len(),
+        world.active_chunk_list_len()
+    );
+    assert!(world.moved_slice().iter().any(|&moved| moved));
+}
+
+#[test]
+fn surface_tension_keeps_puddles_thick() {
+    let run = |tension: bool| {
+        let mut floor = world();
+
+        floor.set_seed(2);
+        floor.set_surface_tension(tension);
+        floor.fill_rect(0, 63, 64, 1, Material::Rock, Tint::None, 0);
+        floor.fill_rect(28, 40, 6, 6, Material::Water, Tint::None, 5);
+        floor.place(5, 50, Material::Water, Tint::None, 5);
+        floor.step(300);
+
+        // A droplet on its own still falls rather than hanging where it was placed.
+        assert!(floor.materials_slice()[50 * 64 + 5] == Material::Air);
+
+        (0..64)
+            .map(|y| {
+                (0..64)
+                    .filter(|&x| floor.materials_slice()[y * 64 + x] == Material::Water)
+                    .count()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let film = run(false);
+    let puddle = run(true);
+
+    assert_eq!(film.iter().sum::<usize>(), 37);
+    assert_eq!(puddle.iter().sum::<usize>(), 37);
+
+    // Without tension the water thins out into a film one cell deep; with it, the puddle holds
+    // onto a second layer.
+    assert_eq!(film[62], 37);
+    assert!(puddle[61] > 0);
+    assert!(puddle[62] < 37);
+}
+
+#[test]
+fn cell_count_follows_the_world_through_a_resize() {
+    let mut world = World::create(40, 24, 16);
+
+    // The views read both buffers as plain bytes.
+    assert_eq!(std::mem::size_of::<Material>(), 1);
+    assert_eq!(std::mem::size_of::<Tint>(), 1);
+
+    assert_eq!(world.cell_count(), 40 * 24);
+
+    world.resize(17, 9, Anchor::TopLeft);
+
+    assert_eq!(world.cell_count(), 17 * 9);
+    assert_eq!(world.materials_slice().len(), world.cell_count());
+    assert_eq!(world.tints_slice().len(), world.cell_count());
+}
+
+#[test]
+fn inverting_a_region_swaps_air_and_sand_but_keeps_rock() {
+    let mut world = world();
+
+    world.fill_rect(0, 60, 8, 4, Material::Sand, Tint::None, 1);
+    world.fill_rect(8, 60, 8, 4, Material::Rock, Tint::None, 0);
+    world.step(100);
+
+    assert!(!world.is_active());
+
+    let before = world.materials_slice().to_vec();
+
+    // Clipped to 4 columns of sand, 8 of rock and 48 of air on each of the last 4 rows.
+    assert_eq!(
+        world.invert_region(4, 60, 100, 100, Material::Sand),
+        4 * (4 + 48)
+    );
+
+    for y in 0..64 {
+        for x in 0..64 {
+            let (was, is) = (before[y * 64 + x], world.materials_slice()[y * 64 + x]);
+
+            if y < 60 || x < 4 || was == Material::Rock {
+                assert!(is == was, "({}, {})", x, y);
+            } else if was == Material::Sand {
+                assert!(is == Material::Air, "({}, {})", x, y);
+            } else {
+                assert!(is == Material::Sand, "({}, {})", x, y);
+            }
+        }
+    }
+
+    assert!(world.is_active());
+}
+
+#[test]
+fn the_layout_matches_the_buffers() {
+    let mut world = World::create(40, 24, 16);
+    let layout = world.layout();
+
+    assert_eq!(layout.element_size, 1);
+    assert_eq!(layout.row_stride, 40);
+    assert!(layout.order == BufferOrder::RowMajor);
+    assert_eq!(world.materials_len(), 40 * 24);
+    assert_eq!(world.tints_len(), 40 * 24);
+
+    // Row-major: the cell at (3, 2) sits 2 strides and 3 elements in.
+    world.place(3, 2, Material::Rock, Tint::None, 0);
+
+    assert!(
+        world.materials_slice()[2 * layout.row_stride + 3 * layout.element_size] == Material::Rock
+    );
+
+    world.resize(17, 9, Anchor::TopLeft);
+
+    assert_eq!(world.layout().row_stride, 17);
+    assert_eq!(world.materials_len(), 17 * 9);
+    assert_eq!(world.tints_len(), 17 * 9);
+}
+
+#[test]
+fn concave_polygons_are_filled_and_clipped_to_the_world() {
+    let mut world = world();
+
+    // A U hanging off the bottom right corner, with its notch partly in the world.
+    let points = [
+        50, 50, 80, 50, 80, 80, 70, 80, 70, 60, 60, 60, 60, 80, 50, 80,
+    ];
+
+    assert_eq!(
+        world.place_polygon(&points, Material::Rock, Tint::None, 0),
+        14 * 14 - 4 * 4
+    );
+
+    for y in 0..64 {
+        for x in 0..64 {
+            let inside = x >= 50 && y >= 50 && !(x >= 60 && y >= 60);
+            let material = world.materials_slice()[y * 64 + x];
+
+            assert!((material == Material::Rock) == inside, "({}, {})", x, y);
+        }
+    }
+
+    assert!(world.is_active());
+}
+
+#[test]
+fn toggling_erosion_wakes_a_settled_world() {
+    let mut world = world();
+
+    world.fill_rect(0, 56, 64, 8, Material::Rock, Tint::None, 0);
+    world.step(100);
+
+    assert!(!world.is_active());
+
+    world.set_erosion(true);
+
+    assert!(world.is_active());
+
+    world.step(100);
+    world.set_erosion(true);
+
+    // Only a change to the rule wakes the world.
+    assert!(!world.is_active());
+}
+
+#[test]
+fn resetting_partway_through_a_tick_starts_the_next_one_fresh() {
+    let mut world = world();
+
+    world.fill_rect(0, 0, 64, 32, Material::Sand, Tint::None, 1);
+    world.set_max_active_chunks(2);
+    world.simulate_region(0, 0, 32, 32);
+    world.simulate_budgeted(0);
+    world.reset();
+
+    assert!(world.tick.is_none());
+    assert!(!world.fenced.any());
+    assert!(world.deferrals.iter().all(|&deferrals| deferrals == 0));
+
+    world.set_max_active_chunks(u32::MAX);
+    world.place(40, 40, Material::Sand, Tint::None, 1);
+
+    while !world.simulate_budgeted(0) {}
+
+    // The whole world is scanned again, not just what was left of the abandoned tick.
+    assert!(world.materials_slice()[41 * 64 + 40] == Material::Sand);
+    assert_eq!(world.tick(), 1);
+}
+
+#[test]
+fn recoloring_can_be_undone_and_ignores_empty_rects() {
+    let mut world = world();
+
+    world.enable_history(4);
+    world.fill_rect(0, 60, 64, 4, Material::Sand, Tint::None, 1);
+    world.start_recording();
+
+    let before = world.state_hash();
+    let length = world.stop_recording().len();
+
+    world.start_recording();
+    world.set_tint_rect(64, 0, 4, 4, Tint::Dark);
+    world.set_tint_rect(0, 70, 4, 4, Tint::Dark);
+
+    // Rects entirely outside the world are neither recorded nor kept in history.
+    assert_eq!(world.stop_recording().len(), length);
+    assert_eq!(world.state_hash(), before);
+
+    world.set_tint_rect(60, 62, 10, 10, Tint::Dark);
+
+    assert!(world.tints_slice()[63 * 64 + 63] == Tint::Dark);
+    assert!(world.undo());
+    assert_eq!(world.state_hash(), before);
+    assert!(world.undo());
+    assert!(world.materials_slice()[63 * 64] == Material::Air);
+}
+
+#[test]
+fn replaying_a_forged_header_fails_instead_of_panicking() {
+    let mut world = World::create(48, 32, 8);
+
+    world.start_recording();
+    world.place(4, 4, Material::Sand, Tint::None, 1);
+    world.simulate();
+
+    let log = world.stop_recording();
+    let forge = |width: u32, height: u32, chunk_size: u32| {
+        let mut forged = log.clone();
+
+        forged[5..9].copy_from_slice(&width.to_le_bytes());
+        forged[9..13].copy_from_slice(&height.to_le_bytes());
+        forged[13..17].copy_from_slice(&chunk_size.to_le_bytes());
+        World::replay(&forged)
+    };
+
+    assert!(forge(48, 32, 8).is_ok());
+    assert!(forge(48, 32, 0).is_err());
+    assert!(forge(48, 32, 49).is_err());
+    assert!(forge(0, 32, 8).is_err());
+    assert!(forge(48, 0, 8).is_err());
+    assert!(forge(u32::MAX, u32::MAX, 8).is_err());
+    assert!(forge(1 << 13, 1 << 13, 8).is_err());
+
+    let mut resized = World::create(48, 32, 8);
+
+    resized.start_recording();
+    resized.resize(50, 40, Anchor::TopLeft);
+
+    let mut log = resized.stop_recording();
+    let op = [OP_RESIZE, 50, 0, 0, 0, 40, 0, 0, 0];
+    let at = log
+        .windows(op.len())
+        .rposition(|window| window == op)
+        .unwrap();
+
+    assert!(World::replay(&log).is_ok());
+
+    log[at + 1..at + 5].copy_from_slice(&(1u32 << 13).to_le_bytes());
+    log[at + 5..at + 9].copy_from_slice(&(1u32 << 13).to_le_bytes());
+
+    assert!(World::replay(&log).is_err());
+}
+
+#[test]
+fn active_cells_count_each_moving_cell_once() {
+    let mut world = world();
+
+    world.fill_rect(0, 60, 64, 4, Material::Rock, Tint::None, 0);
+    world.set_substeps(3);
+    world.place(10, 10, Material::Sand, Tint::None, 0);
+
+    // One grain falling through three substeps makes three moves, but is still one cell.
+    assert_eq!(world.simulate().moved_cells, 3);
+    assert_eq!(world.count_active_cells(), 1);
+
+    world.step(100);
+    world.set_substeps(1);
+    world.simulate();
+
+    assert_eq!(world.count_active_cells(), 0);
+
+    world.place(30, 59, Material::Water, Tint::None, 0);
+    world.place(30, 58, Material::Sand, Tint::None, 0);
+
+    // Sand sinking into water trades places with it: two cells, one move.
+    assert_eq!(world.simulate().moved_cells, 1);
+    assert_eq!(world.count_active_cells(), 2);
+}
+
+#[test]
+fn generated_terrain_only_wakes_its_surface_and_can_be_undone() {
+    let mut world = World::create(64, 128, 16);
+
+    world.enable_history(4);
+    world.place(5, 5, Material::Sand, Tint::None, 1);
+
+    let before = world.state_hash();
+
+    world.generate_terrain(7, &TerrainOptions::new(100, 4, 0));
+
+    let awake: Vec<bool> = (0..4 * 8)
+        .map(|i| world.active_chunks.get(i) || world.forecast.get(i))
+        .collect();
+
+    // The surface lies within the second row of chunks, far above the bedrock.
+    assert!(awake[4..8].iter().all(|&awake| awake));
+    assert!(awake[4 * 4..].iter().all(|&awake| !awake));
+
+    assert!(world.undo());
+    assert_eq!(world.state_hash(), before);
+}
+
+#[test]
+fn every_material_has_its_own_color() {
+    let materials: Vec<Material> = (0..MATERIAL_COUNT as u8)
+        .map(|value| Material::try_from(value).unwrap())
+        .collect();
+
+    assert_eq!(material_color(Material::Air) & 0xff, 0);
+
+    for (i, &material) in materials.iter().enumerate() {
+        assert_eq!(material_color(material), material.props().default_color);
+
+        if material != Material::Air {
+            assert!(material_color(material) & 0xff > 0);
+        }
+
+        for &other in &materials[i + 1..] {
+            assert_ne!(material_color(material), material_color(other));
+        }
+    }
+}
+
+/// A world split into four stripes, with sand, water and smoke all on their way across them.
+fn striped_world() -> World {
+    let mut world = World::create(64, 64, 8);
+
+    world.set_seed(11);
+    world.set_stripe_rows(2);
+    world.fill_rect(0, 62, 64, 2, Material::Rock, Tint::None, 0);
+    world.fill_rect(4, 2, 12, 12, Material::Sand, Tint::None, 0);
+    world.fill_rect(24, 10, 12, 20, Material::Water, Tint::None, 4);
+    world.fill_rect(44, 48, 12, 12, Material::Smoke, Tint::None, 2);
+    world
+}
+
+#[test]
+fn striped_ticks_carry_material_across_stripes() {
+    let mut world = striped_world();
+    let counts = world.counts_slice().to_vec();
+
+    for _ in 0..300 {
+        world.simulate();
+
+        #[cfg(feature = "debug-checks")]
+        world.check_invariants().unwrap();
+    }
+
+    assert_eq!(world.counts_slice(), &counts[..]);
+
+    let rows = |material: Material| {
+        let cells = world.materials_slice();
+
+        (0..64).filter(move |&y| cells[y * 64..(y + 1) * 64].contains(&material))
+    };
+
+    // The sand and water came down through every stripe below them, and the smoke went up.
+    assert!(rows(Material::Sand).all(|y| y >= 48));
+    assert!(rows(Material::Water).all(|y| y >= 48));
+    assert!(rows(Material::Smoke).all(|y| y < 16));
+}
+
+#[test]
+fn stripes_are_at_least_two_chunk_rows() {
+    let mut world = world();
+
+    world.set_stripe_rows(1);
+    assert_eq!(world.stripe_rows, 2);
+
+    world.set_stripe_rows(0);
+    assert_eq!(world.stripe_rows, 0);
+}
+
+#[test]
+fn striped_ticks_replay() {
+    let mut world = striped_world();
+
+    world.set_stripe_rows(0);
+    world.start_recording();
+    world.set_stripe_rows(3);
+    world.step(40);
+
+    let log = world.stop_recording();
+    let replayed = World::replay(&log).unwrap();
+
+    assert_eq!(replayed.state_hash(), world.state_hash());
+    assert_eq!(replayed.stripe_rows, 3);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn striped_ticks_play_out_the_same_on_any_number_of_threads() {
+    let run = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            let mut world = striped_world();
+
+            (0..200)
+                .map(|_| {
+                    world.simulate();
+                    world.state_hash()
+                })
+                .collect::<Vec<u64>>()
+        })
+    };
+
+    assert_eq!(run(1), run(4));
+}
@@ -0,0 +1,97 @@
+//! Plays scripted scenarios and compares every tick against the fixtures in `tests/golden`. Set
+//! `GOLDEN_RECORD=1` to write the fixtures again after an intended change in behavior.
+
+#![cfg(feature = "golden")]
+
+use std::env;
+use std::path::PathBuf;
+
+use sand::golden::Scenario;
+use sand::Material;
+
+fn verify(name: &str, scenario: Scenario) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect();
+
+    let result = if env::var_os("GOLDEN_RECORD").is_some() {
+        scenario.record(&path)
+    } else {
+        scenario.check(&path)
+    };
+
+    if let Err(error) = result {
+        panic!("{}: {}", name, error);
+    }
+}
+
+#[test]
+fn sand_piles_up() {
+    verify(
+        "sand_pile.txt",
+        Scenario::new(24, 16, 8)
+            .fill(0, 10, 0, 4, 4, Material::Sand)
+            .place(6, 12, 0, Material::Sand)
+            .place(12, 11, 0, Material::Sand)
+            .run_for(40),
+    );
+}
+
+#[test]
+fn water_levels_out() {
+    verify(
+        "water_basin.txt",
+        Scenario::new(24, 16, 8)
+            .fill(0, 0, 12, 24, 4, Material::Rock)
+            .fill(0, 2, 0, 6, 6, Material::Water)
+            .run_for(60),
+    );
+}
+
+#[test]
+fn oil_floats_on_water() {
+    verify(
+        "oil_on_water.txt",
+        Scenario::new(24, 16, 8)
+            .fill(0, 4, 10, 16, 6, Material::Water)
+            .fill(0, 8, 0, 8, 4, Material::Oil)
+            .run_for(60),
+    );
+}
+
+#[test]
+fn acid_eats_into_rock() {
+    verify(
+        "acid_on_rock.txt",
+        Scenario::new(24, 16, 8)
+            .fill(0, 0, 8, 24, 8, Material::Rock)
+            .fill(0, 8, 0, 8, 3, Material::Acid)
+            .run_for(60),
+    );
+}
+
+#[test]
+fn fire_burns_through_a_fuse() {
+    verify(
+        "burning_fuse.txt",
+        Scenario::new(24, 16, 8)
+            .fill(0, 0, 12, 24, 1, Material::Rock)
+            .fill(0, 2, 11, 20, 1, Material::Fuse)
+            .place(4, 2, 10, Material::Fire)
+            .run_for(60),
+    );
+}
+
+#[test]
+fn striped_ticks_pour_across_stripes() {
+    verify(
+        "striped_pour.txt",
+        Scenario::new(24, 24, 4)
+            .stripes(2)
+            .fill(0, 0, 22, 24, 2, Material::Rock)
+            .fill(0, 2, 0, 6, 6, Material::Sand)
+            .fill(0, 14, 2, 6, 8, Material::Water)
+            .place(20, 9, 12, Material::Sand)
+            .run_for(60),
+    );
+}